@@ -0,0 +1,498 @@
+//! Instruction and cycle counts for the same scenarios as the criterion
+//! benches, read from the CPU's hardware performance counters via Linux's
+//! `perf_event_open`.
+//!
+//! Unlike wall-clock time, instruction counts don't depend on what else is
+//! running on the machine, so they're stable enough to compare between noisy
+//! CI runs. Where the counters aren't available, e.g., on other platforms, in
+//! most VMs, or with a restrictive `/proc/sys/kernel/perf_event_paranoid`,
+//! this falls back to wall-clock time.
+//!
+//! ```text
+//! cargo bench --bench counters [-- FILTER]
+//! ```
+
+use fastperm::{
+    lanes::LANES,
+    lehmer::LehmerPermuter,
+    select64::{
+        has_pdep, pdep32, pdep32_fallback, select64, select64_fallback, select64_via_pdep32,
+    },
+    BitScatter, SmallIndexPermutations, MAX_PERIOD,
+};
+use rand::{RngCore, SeedableRng};
+use std::{hint::black_box, time::Instant};
+
+#[macro_use]
+mod scenarios;
+
+use scenarios::{
+    bit_scatter_lanes, lehmer_num_idxs, pdep32_inputs, rng, select64_input_classes,
+    select64_inputs, BenchArgs, Inputs, BENCH_SEED, NUM_IDXS, PDEP32_ONE_INPUT, RNG_NUM_IDXS,
+    TAKE_KS,
+};
+
+const NUM_WARMUP_ITERS: u64 = 1_000;
+const NUM_ITERS: u64 = 10_000;
+// Each scenario is measured this many times, keeping the minimum, which is
+// the run least disturbed by interrupts and context switches.
+const NUM_RUNS: usize = 5;
+// `cargo bench -- --test` just checks that everything runs
+const NUM_TEST_ITERS: u64 = 10;
+
+//////////
+// Perf //
+//////////
+
+#[cfg(target_os = "linux")]
+mod perf {
+    use std::{
+        fs::File,
+        io::{self, Read},
+        os::unix::io::{AsRawFd, FromRawFd},
+    };
+
+    const PERF_TYPE_HARDWARE: u32 = 0;
+    const PERF_COUNT_HW_CPU_CYCLES: u64 = 0;
+    const PERF_COUNT_HW_INSTRUCTIONS: u64 = 1;
+
+    const PERF_FORMAT_GROUP: u64 = 1 << 3;
+
+    // `perf_event_attr` flag bits
+    const DISABLED: u64 = 1 << 0;
+    const EXCLUDE_KERNEL: u64 = 1 << 5;
+    const EXCLUDE_HV: u64 = 1 << 6;
+
+    const PERF_EVENT_IOC_ENABLE: libc::c_ulong = 0x2400;
+    const PERF_EVENT_IOC_DISABLE: libc::c_ulong = 0x2401;
+    const PERF_EVENT_IOC_RESET: libc::c_ulong = 0x2403;
+    const PERF_IOC_FLAG_GROUP: libc::c_ulong = 1;
+
+    // The first version of `perf_event_attr` (`PERF_ATTR_SIZE_VER0`), which
+    // has every field we need. The kernel accepts any known version.
+    #[repr(C)]
+    #[derive(Default)]
+    struct PerfEventAttr {
+        type_: u32,
+        size: u32,
+        config: u64,
+        sample_period: u64,
+        sample_type: u64,
+        read_format: u64,
+        flags: u64,
+        wakeup_events: u32,
+        bp_type: u32,
+        config1: u64,
+    }
+
+    pub struct Counts {
+        pub instructions: u64,
+        pub cycles: u64,
+    }
+
+    /// Instruction and cycle counters for this thread's user-space code,
+    /// read together as one group.
+    pub struct Counters {
+        instructions: File,
+        _cycles: File,
+    }
+
+    impl Counters {
+        pub fn open() -> io::Result<Self> {
+            let instructions = open_counter(PERF_COUNT_HW_INSTRUCTIONS, None)?;
+            let cycles = open_counter(PERF_COUNT_HW_CPU_CYCLES, Some(&instructions))?;
+            Ok(Self {
+                instructions,
+                _cycles: cycles,
+            })
+        }
+
+        pub fn measure(&mut self, f: impl FnOnce()) -> io::Result<Counts> {
+            self.ioctl(PERF_EVENT_IOC_RESET)?;
+            self.ioctl(PERF_EVENT_IOC_ENABLE)?;
+            f();
+            self.ioctl(PERF_EVENT_IOC_DISABLE)?;
+
+            // with `PERF_FORMAT_GROUP`: the number of counters, then each
+            // counter's value, in the order they were opened.
+            let mut buf = [0_u8; 3 * 8];
+            self.instructions.read_exact(&mut buf)?;
+            let value = |i: usize| u64::from_ne_bytes(buf[i * 8..(i + 1) * 8].try_into().unwrap());
+            Ok(Counts {
+                instructions: value(1),
+                cycles: value(2),
+            })
+        }
+
+        fn ioctl(&self, request: libc::c_ulong) -> io::Result<()> {
+            let fd = self.instructions.as_raw_fd();
+            if unsafe { libc::ioctl(fd, request as _, PERF_IOC_FLAG_GROUP) } < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(())
+        }
+    }
+
+    fn open_counter(config: u64, leader: Option<&File>) -> io::Result<File> {
+        let attr = PerfEventAttr {
+            type_: PERF_TYPE_HARDWARE,
+            size: std::mem::size_of::<PerfEventAttr>() as u32,
+            config,
+            read_format: PERF_FORMAT_GROUP,
+            // only the leader starts disabled; the rest follow it
+            flags: if leader.is_none() { DISABLED } else { 0 } | EXCLUDE_KERNEL | EXCLUDE_HV,
+            ..Default::default()
+        };
+        let group_fd = leader.map(|leader| leader.as_raw_fd()).unwrap_or(-1);
+        // pid 0 and cpu -1: this thread, on any cpu
+        let fd = unsafe {
+            libc::syscall(
+                libc::SYS_perf_event_open,
+                &attr as *const PerfEventAttr,
+                0,
+                -1,
+                group_fd,
+                0,
+            )
+        };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(unsafe { File::from_raw_fd(fd as _) })
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod perf {
+    use std::io;
+
+    pub struct Counts {
+        pub instructions: u64,
+        pub cycles: u64,
+    }
+
+    pub struct Counters;
+
+    impl Counters {
+        pub fn open() -> io::Result<Self> {
+            Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "perf_event_open is linux-only",
+            ))
+        }
+
+        pub fn measure(&mut self, _f: impl FnOnce()) -> io::Result<Counts> {
+            unreachable!()
+        }
+    }
+}
+
+/////////////
+// Harness //
+/////////////
+
+struct Harness {
+    // `None` when the hardware counters are unavailable and we're measuring
+    // wall-clock time instead
+    counters: Option<perf::Counters>,
+    args: BenchArgs,
+    num_iters: u64,
+    // per-iteration cost of the measurement loop itself
+    baseline: (f64, f64),
+}
+
+impl Harness {
+    fn from_args() -> Self {
+        let args = BenchArgs::from_env();
+        let counters = match perf::Counters::open() {
+            Ok(counters) => Some(counters),
+            Err(err) => {
+                println!(
+                    "hardware counters unavailable ({}); falling back to wall-clock time\n",
+                    err
+                );
+                None
+            }
+        };
+        let mut this = Self {
+            counters,
+            num_iters: if args.test { NUM_TEST_ITERS } else { NUM_ITERS },
+            args,
+            baseline: (0.0, 0.0),
+        };
+        this.baseline = this.measure(1, || black_box(()));
+        this
+    }
+
+    fn print_header(&self) {
+        let columns = if self.counters.is_some() {
+            ("insns/elem", "cycles/elem")
+        } else {
+            ("ns/elem", "")
+        };
+        println!("{:<56} {:>12} {:>12}", "benchmark", columns.0, columns.1);
+    }
+
+    // The minimum per-element cost of `f` over `NUM_RUNS` runs, as
+    // `(instructions, cycles)`, or `(ns, 0)` without counters.
+    fn measure<T>(&mut self, elems_per_iter: u64, mut f: impl FnMut() -> T) -> (f64, f64) {
+        let num_iters = self.num_iters;
+        for _ in 0..NUM_WARMUP_ITERS.min(num_iters) {
+            black_box(f());
+        }
+        let mut run = || {
+            for _ in 0..num_iters {
+                black_box(f());
+            }
+        };
+
+        let (mut min_first, mut min_second) = (u64::MAX, u64::MAX);
+        for _ in 0..NUM_RUNS {
+            let (first, second) = match &mut self.counters {
+                Some(counters) => {
+                    let counts = counters.measure(&mut run).expect("failed to read counters");
+                    (counts.instructions, counts.cycles)
+                }
+                None => {
+                    let start = Instant::now();
+                    run();
+                    (start.elapsed().as_nanos() as u64, 0)
+                }
+            };
+            min_first = min_first.min(first);
+            min_second = min_second.min(second);
+        }
+
+        let num_elems = (num_iters * elems_per_iter) as f64;
+        (min_first as f64 / num_elems, min_second as f64 / num_elems)
+    }
+
+    fn bench<T>(&mut self, id: &str, elems_per_iter: u64, f: impl FnMut() -> T) {
+        if !self.args.matches(id) {
+            return;
+        }
+        let (first, second) = self.measure(elems_per_iter, f);
+        let baseline = (
+            self.baseline.0 / elems_per_iter as f64,
+            self.baseline.1 / elems_per_iter as f64,
+        );
+        let first = (first - baseline.0).max(0.0);
+        if self.counters.is_some() {
+            let second = (second - baseline.1).max(0.0);
+            println!("{:<56} {:>12.2} {:>12.2}", id, first, second);
+        } else {
+            println!("{:<56} {:>12.2}", id, first);
+        }
+    }
+}
+
+// Bench every permuter from `for_each_permuter!` with period `$num_idxs` on
+// `$body`, where `$perm` is bound to the permuter, as `$group/<name>/$num_idxs`.
+macro_rules! bench_permuters {
+    ($h:expr, $group:expr, $num_idxs:expr, $elems:expr, $rng:expr, |$perm:ident| $body:expr) => {{
+        let (group, num_idxs, elems): (&str, u8, u64) = ($group, $num_idxs, $elems);
+        for_each_permuter!(num_idxs, $rng, |name, $perm| {
+            $h.bench(&format!("{}/{}/{}", group, name, num_idxs), elems, || $body)
+        });
+    }};
+}
+
+// The same groups as `benches/index_permutation.rs`.
+fn index_permutation_counters(h: &mut Harness) {
+    for num_idxs in NUM_IDXS {
+        bench_permuters!(
+            h,
+            "index_permutation_all",
+            num_idxs,
+            num_idxs as u64,
+            rng(),
+            |perm| {
+                for _ in 0..num_idxs {
+                    perm.next_index();
+                }
+            }
+        );
+    }
+    for num_idxs in NUM_IDXS {
+        bench_permuters!(
+            h,
+            "index_permutation_iter_period",
+            num_idxs,
+            num_idxs as u64,
+            rng(),
+            |perm| { for _ in perm.iter_period() {} }
+        );
+    }
+    for num_idxs in NUM_IDXS {
+        bench_permuters!(h, "index_permutation_one_idx", num_idxs, 1, rng(), |perm| {
+            perm.next_index()
+        });
+    }
+    for num_idxs in NUM_IDXS {
+        bench_permuters!(
+            h,
+            "index_permutation_reset_and_one_idx",
+            num_idxs,
+            1,
+            rng(),
+            |perm| {
+                perm.reset();
+                perm.next_index()
+            }
+        );
+    }
+    for k in TAKE_KS {
+        let group = format!("index_permutation_take_{}", k);
+        for num_idxs in NUM_IDXS.into_iter().filter(|&num_idxs| num_idxs > k) {
+            bench_permuters!(h, &group, num_idxs, k as u64, rng(), |perm| {
+                for _ in perm.iter().take(k as usize) {}
+            });
+        }
+    }
+    let mut buf = [0_u8; MAX_PERIOD as usize];
+    for num_idxs in NUM_IDXS {
+        bench_permuters!(
+            h,
+            "index_permutation_fill_period",
+            num_idxs,
+            num_idxs as u64,
+            rng(),
+            |perm| { perm.fill_period(&mut buf) }
+        );
+    }
+    for num_idxs in NUM_IDXS {
+        bench_permuters!(
+            h,
+            "index_permutation_fill",
+            num_idxs,
+            num_idxs as u64,
+            rng(),
+            |perm| { perm.fill(&mut buf[..num_idxs as usize]) }
+        );
+    }
+    for num_idxs in NUM_IDXS {
+        let (mut bit_scatter_x8, mut bit_scatters) = bit_scatter_lanes(num_idxs);
+        let elems = num_idxs as u64 * LANES as u64;
+        h.bench(
+            &format!("index_permutation_x8/bit_scatter_x8/{}", num_idxs),
+            elems,
+            || {
+                for _ in 0..num_idxs {
+                    black_box(bit_scatter_x8.next_indices());
+                }
+            },
+        );
+        h.bench(
+            &format!("index_permutation_x8/bit_scatter_8_scalar/{}", num_idxs),
+            elems,
+            || {
+                for _ in 0..num_idxs {
+                    for bit_scatter in &mut bit_scatters {
+                        black_box(bit_scatter.next_index());
+                    }
+                }
+            },
+        );
+    }
+    for_each_rng!(rng_counters_for(h));
+    for num_idxs in lehmer_num_idxs() {
+        let mut lehmer = LehmerPermuter::new(rng(), num_idxs);
+        let mut bit_scatter = BitScatter::new(rng(), num_idxs);
+        h.bench(
+            &format!("index_permutation_lehmer_iter_period/lehmer/{}", num_idxs),
+            num_idxs as u64,
+            || for _ in lehmer.iter_period() {},
+        );
+        h.bench(
+            &format!(
+                "index_permutation_lehmer_iter_period/bit_scatter/{}",
+                num_idxs
+            ),
+            num_idxs as u64,
+            || for _ in bit_scatter.iter_period() {},
+        );
+    }
+}
+
+fn rng_counters_for<R: RngCore + SeedableRng>(h: &mut Harness, rng_name: &str) {
+    let group = format!("index_permutation_rng_{}", rng_name);
+    for num_idxs in RNG_NUM_IDXS {
+        bench_permuters!(
+            h,
+            &group,
+            num_idxs,
+            num_idxs as u64,
+            R::seed_from_u64(BENCH_SEED),
+            |perm| { for _ in perm.iter_period() {} }
+        );
+    }
+}
+
+// Bench every select implementation on `inputs` as `$group/<impl>/$param`;
+// see `bench_select_impls` in `benches/select64.rs`.
+fn select_impls_counters(h: &mut Harness, group: &str, param: Option<&str>, inputs: &Inputs) {
+    let id = |name: &str| match param {
+        Some(param) => format!("{}/{}/{}", group, name, param),
+        None => format!("{}/{}", group, name),
+    };
+    let num_inputs = inputs.len() as u64;
+    h.bench(&id("select64"), num_inputs, || {
+        for &(idx, mask) in inputs {
+            black_box(select64(idx, mask));
+        }
+    });
+    if has_pdep() {
+        h.bench(&id("select64_via_pdep32"), num_inputs, || {
+            for &(idx, mask) in inputs {
+                black_box(select64_via_pdep32(idx, mask));
+            }
+        });
+    }
+    h.bench(&id("select64_fallback"), num_inputs, || {
+        for &(idx, mask) in inputs {
+            black_box(select64_fallback(idx, mask));
+        }
+    });
+}
+
+// The same groups as `benches/select64.rs`.
+fn select64_counters(h: &mut Harness) {
+    let pdep_inputs = pdep32_inputs();
+    let num_inputs = pdep_inputs.len() as u64;
+    if has_pdep() {
+        h.bench("pdep32/pdep32", num_inputs, || {
+            for &(src, mask) in &pdep_inputs {
+                black_box(pdep32(src, mask));
+            }
+        });
+    }
+    h.bench("pdep32/pdep32_fallback", num_inputs, || {
+        for &(src, mask) in &pdep_inputs {
+            black_box(pdep32_fallback(src, mask));
+        }
+    });
+
+    let (src, mask) = PDEP32_ONE_INPUT;
+    if has_pdep() {
+        h.bench("pdep32_one_input/pdep32", 1, || {
+            pdep32(black_box(src), black_box(mask))
+        });
+    }
+    h.bench("pdep32_one_input/pdep32_fallback", 1, || {
+        pdep32_fallback(black_box(src), black_box(mask))
+    });
+
+    select_impls_counters(h, "select64", None, &select64_inputs());
+    for (group, classes) in select64_input_classes() {
+        for (param, inputs) in classes {
+            select_impls_counters(h, group, Some(&param), &inputs);
+        }
+    }
+}
+
+fn main() {
+    let mut harness = Harness::from_args();
+    harness.print_header();
+    index_permutation_counters(&mut harness);
+    select64_counters(&mut harness);
+}
@@ -1,174 +1,159 @@
-use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
 use fastperm::{
-    BitScatter, Shuffle, ShuffleArray, ShuffleArrayIncremental, SmallIndexPermutations, MAX_PERIOD,
+    lanes::LANES, lehmer::LehmerPermuter, BitScatter, SmallIndexPermutations, MAX_PERIOD,
 };
-use rand::SeedableRng;
-use rand_xoshiro::Xoroshiro64Star;
+use rand::{RngCore, SeedableRng};
 
-const BENCH_SEED: u64 = 0xDEAD_BEEF_F000_BA55;
+#[macro_use]
+mod scenarios;
 
-fn rng() -> Xoroshiro64Star {
-    Xoroshiro64Star::seed_from_u64(BENCH_SEED)
+use scenarios::{
+    bit_scatter_lanes, lehmer_num_idxs, rng, BENCH_SEED, NUM_IDXS, RNG_NUM_IDXS, TAKE_KS,
+};
+
+// Benchmark `$body` once per permuter from `for_each_permuter!`, each with
+// period `$num_idxs` and bound to `$perm`.
+macro_rules! bench_permuters {
+    ($g:expr, $num_idxs:expr, $rng:expr, |$perm:ident| $body:expr) => {{
+        let num_idxs: u8 = $num_idxs;
+        for_each_permuter!(num_idxs, $rng, |name, $perm| {
+            $g.bench_function(BenchmarkId::new(name, num_idxs), |b| b.iter(|| $body))
+        });
+    }};
 }
 
 fn index_permutation_bench(c: &mut Criterion) {
-    let num_idxs: u8 = MAX_PERIOD;
-
-    let mut shuffle = Shuffle::new(rng(), num_idxs);
-    let mut shuffle_array = ShuffleArray::new(rng(), num_idxs);
-    let mut shuffle_array_incr = ShuffleArrayIncremental::new(rng(), num_idxs);
-    let mut bit_scatter = BitScatter::new(rng(), num_idxs);
-    let mut rng2 = rng();
-    let mut bit_scatter_rng_ref = BitScatter::new(&mut rng2, num_idxs);
-
-    let mut g = c.benchmark_group("index_permutation_64_all");
-
-    g.throughput(Throughput::Elements(num_idxs as u64));
-    g.bench_function("shuffle", |b| {
-        b.iter(|| {
-            for _ in 0..num_idxs {
-                shuffle.next_index();
-            }
-        })
-    });
-    g.bench_function("shuffle_array", |b| {
-        b.iter(|| {
+    let mut g = c.benchmark_group("index_permutation_all");
+    for num_idxs in NUM_IDXS {
+        g.throughput(Throughput::Elements(num_idxs as u64));
+        bench_permuters!(g, num_idxs, rng(), |perm| {
             for _ in 0..num_idxs {
-                shuffle_array.next_index();
+                perm.next_index();
             }
-        })
-    });
-    g.bench_function("shuffle_array_incr", |b| {
-        b.iter(|| {
-            for _ in 0..num_idxs {
-                shuffle_array_incr.next_index();
-            }
-        })
-    });
-    g.bench_function("bit_scatter", |b| {
-        b.iter(|| {
-            for _ in 0..num_idxs {
-                bit_scatter.next_index();
-            }
-        })
-    });
-    g.bench_function("bit_scatter_rng_ref", |b| {
-        b.iter(|| {
-            for _ in 0..num_idxs {
-                bit_scatter_rng_ref.next_index();
-            }
-        })
-    });
-
+        });
+    }
     g.finish();
 
-    let mut g = c.benchmark_group("index_permutation_64_iter_period");
-
-    g.throughput(Throughput::Elements(num_idxs as u64));
-    g.bench_function("shuffle", |b| b.iter(|| for _ in shuffle.iter_period() {}));
-    g.bench_function("shuffle_array", |b| {
-        b.iter(|| for _ in shuffle_array.iter_period() {})
-    });
-    g.bench_function("shuffle_array_incr", |b| {
-        b.iter(|| for _ in shuffle_array_incr.iter_period() {})
-    });
-    g.bench_function("bit_scatter", |b| {
-        b.iter(|| for _ in bit_scatter.iter_period() {})
-    });
-    g.bench_function("bit_scatter_rng_ref", |b| {
-        b.iter(|| for _ in bit_scatter_rng_ref.iter_period() {})
-    });
-
+    let mut g = c.benchmark_group("index_permutation_iter_period");
+    for num_idxs in NUM_IDXS {
+        g.throughput(Throughput::Elements(num_idxs as u64));
+        bench_permuters!(g, num_idxs, rng(), |perm| for _ in perm.iter_period() {});
+    }
     g.finish();
 
-    let mut g = c.benchmark_group("index_permutation_64_one_idx");
+    let mut g = c.benchmark_group("index_permutation_one_idx");
+    g.throughput(Throughput::Elements(1));
+    for num_idxs in NUM_IDXS {
+        bench_permuters!(g, num_idxs, rng(), |perm| perm.next_index());
+    }
+    g.finish();
 
+    let mut g = c.benchmark_group("index_permutation_reset_and_one_idx");
     g.throughput(Throughput::Elements(1));
-    g.bench_function("shuffle", |b| b.iter(|| shuffle.next_index()));
-    g.bench_function("shuffle_array", |b| b.iter(|| shuffle_array.next_index()));
-    g.bench_function("shuffle_array_incr", |b| {
-        b.iter(|| shuffle_array_incr.next_index())
-    });
-    g.bench_function("bit_scatter", |b| b.iter(|| bit_scatter.next_index()));
-    g.bench_function("bit_scatter_rng_ref", |b| {
-        b.iter(|| bit_scatter_rng_ref.next_index())
-    });
+    for num_idxs in NUM_IDXS {
+        bench_permuters!(g, num_idxs, rng(), |perm| {
+            perm.reset();
+            perm.next_index()
+        });
+    }
+    g.finish();
 
+    // partial periods: reset, then take only the first `k` indices
+    for k in TAKE_KS {
+        let mut g = c.benchmark_group(format!("index_permutation_take_{}", k));
+        g.throughput(Throughput::Elements(k as u64));
+        for num_idxs in NUM_IDXS.into_iter().filter(|&num_idxs| num_idxs > k) {
+            bench_permuters!(
+                g,
+                num_idxs,
+                rng(),
+                |perm| for _ in perm.iter().take(k as usize) {}
+            );
+        }
+        g.finish();
+    }
+
+    let mut buf = [0_u8; MAX_PERIOD as usize];
+
+    let mut g = c.benchmark_group("index_permutation_fill_period");
+    for num_idxs in NUM_IDXS {
+        g.throughput(Throughput::Elements(num_idxs as u64));
+        bench_permuters!(g, num_idxs, rng(), |perm| perm.fill_period(&mut buf));
+    }
     g.finish();
 
-    let mut g = c.benchmark_group("index_permutation_64_reset_and_one_idx");
+    let mut g = c.benchmark_group("index_permutation_fill");
+    for num_idxs in NUM_IDXS {
+        g.throughput(Throughput::Elements(num_idxs as u64));
+        bench_permuters!(g, num_idxs, rng(), |perm| perm
+            .fill(&mut buf[..num_idxs as usize]));
+    }
+    g.finish();
 
-    g.throughput(Throughput::Elements(1));
-    g.bench_function("shuffle", |b| {
-        b.iter(|| {
-            shuffle.reset();
-            shuffle.next_index();
-        })
-    });
-    g.bench_function("shuffle_array", |b| {
-        b.iter(|| {
-            shuffle_array.reset();
-            shuffle_array.next_index();
-        })
-    });
-    g.bench_function("shuffle_array_incr", |b| {
-        b.iter(|| {
-            shuffle_array_incr.reset();
-            shuffle_array_incr.next_index();
-        })
-    });
-    g.bench_function("bit_scatter", |b| {
-        b.iter(|| {
-            bit_scatter.reset();
-            bit_scatter.next_index();
-        })
-    });
-    g.bench_function("bit_scatter_rng_ref", |b| {
-        b.iter(|| {
-            bit_scatter_rng_ref.reset();
-            bit_scatter_rng_ref.next_index();
-        })
-    });
+    let mut g = c.benchmark_group("index_permutation_x8");
+    for num_idxs in NUM_IDXS {
+        let (mut bit_scatter_x8, mut bit_scatters) = bit_scatter_lanes(num_idxs);
+
+        g.throughput(Throughput::Elements(num_idxs as u64 * LANES as u64));
+        g.bench_function(BenchmarkId::new("bit_scatter_x8", num_idxs), |b| {
+            b.iter(|| {
+                for _ in 0..num_idxs {
+                    black_box(bit_scatter_x8.next_indices());
+                }
+            })
+        });
+        g.bench_function(BenchmarkId::new("bit_scatter_8_scalar", num_idxs), |b| {
+            b.iter(|| {
+                for _ in 0..num_idxs {
+                    for bit_scatter in &mut bit_scatters {
+                        black_box(bit_scatter.next_index());
+                    }
+                }
+            })
+        });
+    }
+    g.finish();
+}
 
+// Permuter speed is dominated by rng cost, so compare every permuter on each
+// rng backend, both owned and by `&mut` reference.
+fn rng_bench_for<R: RngCore + SeedableRng>(c: &mut Criterion, rng_name: &str) {
+    let mut g = c.benchmark_group(format!("index_permutation_rng_{}", rng_name));
+    for num_idxs in RNG_NUM_IDXS {
+        g.throughput(Throughput::Elements(num_idxs as u64));
+        bench_permuters!(g, num_idxs, R::seed_from_u64(BENCH_SEED), |perm| {
+            for _ in perm.iter_period() {}
+        });
+    }
     g.finish();
+}
+
+fn rng_bench(c: &mut Criterion) {
+    for_each_rng!(rng_bench_for(c));
+}
+
+fn lehmer_bench(c: &mut Criterion) {
+    let mut g = c.benchmark_group("index_permutation_lehmer_iter_period");
+    for num_idxs in lehmer_num_idxs() {
+        let mut lehmer = LehmerPermuter::new(rng(), num_idxs);
+        let mut bit_scatter = BitScatter::new(rng(), num_idxs);
+
+        g.throughput(Throughput::Elements(num_idxs as u64));
+        g.bench_function(BenchmarkId::new("lehmer", num_idxs), |b| {
+            b.iter(|| for _ in lehmer.iter_period() {})
+        });
+        g.bench_function(BenchmarkId::new("bit_scatter", num_idxs), |b| {
+            b.iter(|| for _ in bit_scatter.iter_period() {})
+        });
+    }
 
-    // let mut g = c.benchmark_group("index_permutation_64_take_8");
-    //
-    // g.throughput(Throughput::Elements(8));
-    // g.bench_function("shuffle", |b| b.iter(|| for _ in shuffle.iter().take(8) {}));
-    // g.bench_function("bit_scatter", |b| {
-    //     b.iter(|| for _ in bit_scatter.iter().take(8) {})
-    // });
-    //
-    // g.finish();
-
-    // let num_idxs: u8 = 8;
-    //
-    // let rng1 = Xoroshiro64Star::seed_from_u64(BENCH_SEED);
-    // let rng2 = Xoroshiro64Star::seed_from_u64(BENCH_SEED);
-    // let mut shuffle = Shuffle::new(rng1, num_idxs);
-    // let mut bit_scatter = BitScatter::new(rng2, num_idxs);
-    //
-    // let mut g = c.benchmark_group("index_permutation_8_all");
-    //
-    // g.throughput(Throughput::Elements(num_idxs as u64));
-    // g.bench_function("shuffle", |b| b.iter(|| for _ in shuffle.iter_period() {}));
-    // g.bench_function("bit_scatter", |b| {
-    //     b.iter(|| for _ in bit_scatter.iter_period() {})
-    // });
-    //
-    // g.finish();
-    //
-    // let mut g = c.benchmark_group("index_permutation_8_take_3");
-    //
-    // g.throughput(Throughput::Elements(3));
-    // g.bench_function("shuffle", |b| b.iter(|| for _ in shuffle.iter().take(3) {}));
-    // g.bench_function("bit_scatter", |b| {
-    //     b.iter(|| for _ in bit_scatter.iter().take(3) {})
-    // });
-    //
-    // g.finish();
+    g.finish();
 }
 
-criterion_group!(index_permutation_benches, index_permutation_bench);
+criterion_group!(
+    index_permutation_benches,
+    index_permutation_bench,
+    rng_bench,
+    lehmer_bench
+);
 criterion_main!(index_permutation_benches);
@@ -0,0 +1,216 @@
+//! Per-call latency distributions for each permuter.
+//!
+//! Criterion only reports mean times, which hide the `O(n)` reshuffle that
+//! `Shuffle` and `ShuffleArray` do on the first call of each period, or the
+//! whole periods that `Derangement` and `NoRepeat` may reject and redraw. This
+//! times every `next_index` call individually and reports the tail instead.
+//!
+//! ```text
+//! cargo bench --bench latency [-- FILTER]
+//! ```
+
+use fastperm::{
+    lehmer::{LehmerPermuter, MAX_LEHMER_PERIOD},
+    weighted::WeightedPermuter,
+    AutoPermuter, BitScatter, Derangement, NoRepeat, Sattolo, Shuffle, ShuffleArray,
+    ShuffleArrayIncremental, ShuffleBag, SmallIndexPermutations,
+};
+use std::hint::black_box;
+
+mod scenarios;
+
+use scenarios::{rng, BenchArgs, NUM_IDXS};
+
+const NUM_WARMUP_CALLS: usize = 100_000;
+const NUM_SAMPLES: usize = 1_000_000;
+// `cargo bench -- --test` just checks that everything runs
+const NUM_TEST_SAMPLES: usize = 1_000;
+
+///////////
+// Timer //
+///////////
+
+// The timestamp counter on x86, which is much cheaper to read than `Instant`.
+#[cfg(target_arch = "x86_64")]
+mod timer {
+    use std::arch::x86_64::{_mm_lfence, _rdtsc};
+
+    pub const UNIT: &str = "tsc cycles";
+
+    #[inline(always)]
+    pub fn now() -> u64 {
+        // fence so the timestamp can't be taken before the preceding
+        // instructions, or after the following ones, have executed.
+        unsafe {
+            _mm_lfence();
+            let tsc = _rdtsc();
+            _mm_lfence();
+            tsc
+        }
+    }
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+mod timer {
+    use std::{sync::OnceLock, time::Instant};
+
+    pub const UNIT: &str = "ns";
+
+    #[inline(always)]
+    pub fn now() -> u64 {
+        static EPOCH: OnceLock<Instant> = OnceLock::new();
+        EPOCH.get_or_init(Instant::now).elapsed().as_nanos() as u64
+    }
+}
+
+// The median cost of reading the timer twice, which gets subtracted from
+// every sample.
+fn timer_overhead() -> u64 {
+    let mut samples = (0..NUM_WARMUP_CALLS)
+        .map(|_| {
+            let start = timer::now();
+            timer::now() - start
+        })
+        .collect::<Vec<_>>();
+    samples.sort_unstable();
+    samples[samples.len() / 2]
+}
+
+/////////////
+// Harness //
+/////////////
+
+struct Latencies {
+    p50: u64,
+    p99: u64,
+    p999: u64,
+    max: u64,
+}
+
+impl Latencies {
+    fn from_samples(mut samples: Vec<u64>) -> Self {
+        samples.sort_unstable();
+        let percentile = |p: f64| {
+            let rank = (p * samples.len() as f64).ceil() as usize;
+            samples[rank.clamp(1, samples.len()) - 1]
+        };
+        Self {
+            p50: percentile(0.50),
+            p99: percentile(0.99),
+            p999: percentile(0.999),
+            max: *samples.last().unwrap(),
+        }
+    }
+}
+
+struct Harness {
+    args: BenchArgs,
+    num_samples: usize,
+    overhead: u64,
+}
+
+impl Harness {
+    fn from_args() -> Self {
+        let args = BenchArgs::from_env();
+        Self {
+            num_samples: if args.test {
+                NUM_TEST_SAMPLES
+            } else {
+                NUM_SAMPLES
+            },
+            args,
+            overhead: timer_overhead(),
+        }
+    }
+
+    fn bench<P: SmallIndexPermutations>(&self, name: &str, perm: P) {
+        let n = perm.period();
+        self.bench_with(name, n, perm, P::next_index, P::reset);
+    }
+
+    // Like `bench`, but for anything with a `next` and `reset`, e.g., a
+    // `ShuffleBag`.
+    fn bench_with<T>(
+        &self,
+        name: &str,
+        n: u8,
+        mut perm: T,
+        next: impl Fn(&mut T) -> u8,
+        reset: impl Fn(&mut T),
+    ) {
+        let id = format!("{}/{}", name, n);
+        if !self.args.matches(&id) {
+            return;
+        }
+
+        for _ in 0..NUM_WARMUP_CALLS.min(self.num_samples) {
+            black_box(next(&mut perm));
+        }
+        // start on a period boundary, so every run sees the same mix of
+        // calls within the period
+        reset(&mut perm);
+
+        let mut samples = vec![0_u64; self.num_samples];
+        for sample in &mut samples {
+            let start = timer::now();
+            black_box(next(&mut perm));
+            *sample = (timer::now() - start).saturating_sub(self.overhead);
+        }
+
+        let latencies = Latencies::from_samples(samples);
+        println!(
+            "{:<24} {:>8} {:>8} {:>8} {:>8}",
+            id, latencies.p50, latencies.p99, latencies.p999, latencies.max
+        );
+    }
+}
+
+fn main() {
+    let harness = Harness::from_args();
+
+    println!(
+        "next_index latency in {} (timer overhead of {} subtracted)\n",
+        timer::UNIT,
+        harness.overhead
+    );
+    println!(
+        "{:<24} {:>8} {:>8} {:>8} {:>8}",
+        "permuter/n", "p50", "p99", "p99.9", "max"
+    );
+
+    for num_idxs in NUM_IDXS {
+        harness.bench("shuffle", Shuffle::new(rng(), num_idxs));
+        harness.bench("shuffle_array", ShuffleArray::new(rng(), num_idxs));
+        harness.bench(
+            "shuffle_array_incr",
+            ShuffleArrayIncremental::new(rng(), num_idxs),
+        );
+        harness.bench("bit_scatter", BitScatter::new(rng(), num_idxs));
+        harness.bench("sattolo", Sattolo::new(rng(), num_idxs));
+        // rejection sampling makes these two the likeliest to have long tails
+        if num_idxs >= 2 {
+            harness.bench("derangement", Derangement::new(rng(), num_idxs));
+            harness.bench(
+                "no_repeat",
+                NoRepeat::new(ShuffleArrayIncremental::new(rng(), num_idxs)),
+            );
+        }
+        harness.bench("auto", AutoPermuter::new(rng(), num_idxs));
+        if num_idxs <= MAX_LEHMER_PERIOD {
+            harness.bench("lehmer", LehmerPermuter::new(rng(), num_idxs));
+        }
+        let weights = (1..=num_idxs).map(f64::from).collect::<Vec<_>>();
+        harness.bench("weighted", WeightedPermuter::new(rng(), &weights));
+        // each of `n / 2` items twice per period
+        if num_idxs % 2 == 0 {
+            let counts = vec![2; num_idxs as usize / 2];
+            harness.bench_with(
+                "shuffle_bag",
+                num_idxs,
+                ShuffleBag::new(rng(), &counts),
+                ShuffleBag::next_item,
+                ShuffleBag::reset,
+            );
+        }
+    }
+}
@@ -0,0 +1,251 @@
+//! The scenarios shared by the criterion benches and the custom-harness ones,
+//! `benches/counters.rs` and `benches/latency.rs`, so they all measure the
+//! same things: the permuters, the period and prefix sweeps, the rngs, and the
+//! `select64` input classes. Also the custom harnesses' argument parsing.
+
+// each bench only uses some of these
+#![allow(dead_code, unused_macros)]
+
+use fastperm::{
+    fill_mask,
+    lanes::{BitScatterX8, LANES},
+    lehmer::MAX_LEHMER_PERIOD,
+    subset::random_subset_mask,
+    BitScatter, SmallIndexPermutations, MAX_PERIOD,
+};
+use rand::{rngs::SmallRng, Rng, RngCore, SeedableRng};
+use rand_xoshiro::Xoroshiro64Star;
+
+pub const BENCH_SEED: u64 = 0xDEAD_BEEF_F000_BA55;
+
+pub fn rng() -> Xoroshiro64Star {
+    Xoroshiro64Star::seed_from_u64(BENCH_SEED)
+}
+
+/// The arguments of a custom-harness bench: `cargo bench --bench NAME [--
+/// [--test] [FILTER]]`.
+pub struct BenchArgs {
+    /// Only run the benches whose id contains this.
+    pub filter: Option<String>,
+    /// `cargo bench -- --test` just checks that everything runs.
+    pub test: bool,
+}
+
+impl BenchArgs {
+    pub fn from_env() -> Self {
+        // `cargo bench` passes `--bench`; ignore any flags besides `--test`
+        let args = std::env::args().skip(1).collect::<Vec<_>>();
+        Self {
+            test: args.iter().any(|arg| arg == "--test"),
+            filter: args.into_iter().find(|arg| !arg.starts_with("--")),
+        }
+    }
+
+    pub fn matches(&self, id: &str) -> bool {
+        self.filter
+            .as_deref()
+            .is_none_or(|filter| id.contains(filter))
+    }
+}
+
+////////////////////////
+// Index permutations //
+////////////////////////
+
+// The period sizes every group is swept over.
+pub const NUM_IDXS: [u8; 8] = [1, 2, 4, 8, 16, 32, 48, 64];
+
+// The prefix lengths for the partial-period `take_k` groups.
+pub const TAKE_KS: [u8; 3] = [1, 3, 8];
+
+// The period sizes for the rng matrix, which would be too large swept over
+// all of `NUM_IDXS`.
+pub const RNG_NUM_IDXS: [u8; 2] = [8, 64];
+
+// The period sizes for the Lehmer group: `NUM_IDXS` up to its maximum period.
+pub fn lehmer_num_idxs() -> impl Iterator<Item = u8> {
+    NUM_IDXS
+        .into_iter()
+        .filter(|&num_idxs| num_idxs < MAX_LEHMER_PERIOD)
+        .chain([MAX_LEHMER_PERIOD])
+}
+
+// Run `$body` once per permuter, each with period `$num_idxs`, with `$name`
+// bound to the permuter's name and `$perm` to the permuter. `$rng` builds a
+// fresh rng and is evaluated once per permuter. A macro rather than a `dyn`
+// loop, so every permuter is monomorphised like it would be in real use.
+macro_rules! for_each_permuter {
+    ($num_idxs:expr, $rng:expr, |$name:ident, $perm:ident| $body:expr) => {{
+        let num_idxs: u8 = $num_idxs;
+        let mut rng2 = $rng;
+        for_each_permuter!(
+            @one "shuffle",
+            fastperm::Shuffle::new($rng, num_idxs),
+            |$name, $perm| $body
+        );
+        for_each_permuter!(
+            @one "shuffle_array",
+            fastperm::ShuffleArray::new($rng, num_idxs),
+            |$name, $perm| $body
+        );
+        for_each_permuter!(
+            @one "shuffle_array_incr",
+            fastperm::ShuffleArrayIncremental::new($rng, num_idxs),
+            |$name, $perm| $body
+        );
+        for_each_permuter!(
+            @one "bit_scatter",
+            fastperm::BitScatter::new($rng, num_idxs),
+            |$name, $perm| $body
+        );
+        for_each_permuter!(
+            @one "bit_scatter_rng_ref",
+            fastperm::BitScatter::new(&mut rng2, num_idxs),
+            |$name, $perm| $body
+        );
+    }};
+    (@one $name_str:expr, $init:expr, |$name:ident, $perm:ident| $body:expr) => {{
+        let $name: &str = $name_str;
+        let mut $perm = $init;
+        $body;
+    }};
+}
+
+// Call `$f::<R>($args..., name)` for every rng backend `R` in the rng matrix.
+macro_rules! for_each_rng {
+    ($f:ident($($arg:expr),*)) => {{
+        $f::<rand_xoshiro::Xoroshiro64Star>($($arg,)* "xoroshiro64star");
+        $f::<rand_xoshiro::Xoroshiro128PlusPlus>($($arg,)* "xoroshiro128plusplus");
+        $f::<rand_xoshiro::Xoshiro256PlusPlus>($($arg,)* "xoshiro256plusplus");
+        $f::<rand_xoshiro::SplitMix64>($($arg,)* "splitmix64");
+        $f::<rand_pcg::Pcg32>($($arg,)* "pcg32");
+        $f::<rand_chacha::ChaCha8Rng>($($arg,)* "chacha8");
+    }};
+}
+
+// A `BitScatterX8`, and `LANES` scalar `BitScatter`s to compare it against.
+pub fn bit_scatter_lanes(
+    num_idxs: u8,
+) -> (
+    BitScatterX8<Xoroshiro64Star>,
+    Vec<BitScatter<Xoroshiro64Star>>,
+) {
+    let bit_scatters = (0..LANES as u64)
+        .map(|lane| BitScatter::new(Xoroshiro64Star::seed_from_u64(BENCH_SEED + lane), num_idxs))
+        .collect();
+    (BitScatterX8::new(rng(), num_idxs), bit_scatters)
+}
+
+//////////////
+// select64 //
+//////////////
+
+pub const NUM_INPUTS: usize = 1024;
+
+pub type Inputs = Vec<(u8, u64)>;
+
+pub const PDEP32_ONE_INPUT: (u32, u32) = (0x5555_3333, 0xF0F0_550F);
+
+pub fn pdep32_inputs() -> Vec<(u32, u32)> {
+    let mut rng = SmallRng::seed_from_u64(BENCH_SEED);
+    (0..NUM_INPUTS)
+        .map(|_| (rng.next_u32(), rng.next_u32()))
+        .collect()
+}
+
+fn sample_idx_and_mask64<R: Rng>(rng: &mut R) -> (u8, u64) {
+    let idx = rng.gen_range(0..MAX_PERIOD);
+    let mask_bits = rng.gen_range(idx + 1..=MAX_PERIOD);
+    let mut bit_scatter = BitScatter::new(rng, MAX_PERIOD);
+    let bits_perm = bit_scatter.iter().take(mask_bits as usize);
+    let mask = fill_mask(bits_perm).0;
+    (idx, mask)
+}
+
+// The inputs for the plain `select64` group.
+pub fn select64_inputs() -> Inputs {
+    let mut rng = SmallRng::seed_from_u64(BENCH_SEED);
+    (0..NUM_INPUTS)
+        .map(|_| sample_idx_and_mask64(&mut rng))
+        .collect()
+}
+
+// The mask popcounts for the density-stratified inputs.
+const MASK_BITS: [u8; 7] = [1, 2, 8, 16, 32, 48, 64];
+
+// `NUM_INPUTS` masks with exactly `mask_bits` bits set, each with an `idx`
+// sampled from `idxs(mask_bits)`.
+fn sample_inputs_with_density<R: Rng>(
+    rng: &mut R,
+    mask_bits: u8,
+    idxs: impl Fn(u8) -> std::ops::Range<u8>,
+) -> Inputs {
+    (0..NUM_INPUTS)
+        .map(|_| {
+            let mask = random_subset_mask(rng, MAX_PERIOD, mask_bits);
+            (rng.gen_range(idxs(mask_bits)), mask)
+        })
+        .collect()
+}
+
+// Each `select64_*` group, with its input classes as `(param, inputs)`.
+pub fn select64_input_classes() -> Vec<(&'static str, Vec<(String, Inputs)>)> {
+    let mut rng = SmallRng::seed_from_u64(BENCH_SEED);
+
+    let density = MASK_BITS
+        .into_iter()
+        .map(|mask_bits| {
+            let inputs = sample_inputs_with_density(&mut rng, mask_bits, |bits| 0..bits);
+            (mask_bits.to_string(), inputs)
+        })
+        .collect();
+
+    // the binary search fallback and `select64_via_pdep32` both branch on
+    // which half of the mask the selected bit lands in.
+    let idx = vec![
+        (
+            "low".to_owned(),
+            sample_inputs_with_density(&mut rng, 32, |bits| 0..bits / 4),
+        ),
+        (
+            "high".to_owned(),
+            sample_inputs_with_density(&mut rng, 32, |bits| bits * 3 / 4..bits),
+        ),
+    ];
+
+    let single_bit = (0..NUM_INPUTS)
+        .map(|_| (0, 1 << rng.gen_range(0..64)))
+        .collect();
+    let all_ones = (0..NUM_INPUTS)
+        .map(|_| (rng.gen_range(0..MAX_PERIOD), u64::MAX))
+        .collect();
+    let high_half_only = (0..NUM_INPUTS)
+        .map(|_| {
+            let mask_bits = rng.gen_range(1..=32);
+            let mask = random_subset_mask(&mut rng, 32, mask_bits) << 32;
+            (rng.gen_range(0..mask_bits), mask)
+        })
+        .collect();
+    let alternating = (0..NUM_INPUTS)
+        .map(|i| {
+            let mask = if i & 1 == 0 {
+                0x5555_5555_5555_5555
+            } else {
+                0xAAAA_AAAA_AAAA_AAAA
+            };
+            (rng.gen_range(0..32), mask)
+        })
+        .collect();
+    let adversarial = vec![
+        ("single_bit".to_owned(), single_bit),
+        ("all_ones".to_owned(), all_ones),
+        ("high_half_only".to_owned(), high_half_only),
+        ("alternating".to_owned(), alternating),
+    ];
+
+    vec![
+        ("select64_density", density),
+        ("select64_idx", idx),
+        ("select64_adversarial", adversarial),
+    ]
+}
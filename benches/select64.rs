@@ -1,30 +1,17 @@
-use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
-use fastperm::{
-    fill_mask,
-    select64::{pdep32, pdep32_fallback, select64, select64_fallback, select64_via_pdep32},
-    BitScatter, SmallIndexPermutations, MAX_PERIOD,
+use criterion::{
+    black_box, criterion_group, criterion_main, measurement::WallTime, BenchmarkGroup, BenchmarkId,
+    Criterion, Throughput,
+};
+use fastperm::select64::{
+    pdep32, pdep32_fallback, select64, select64_fallback, select64_via_pdep32,
 };
-use rand::{rngs::SmallRng, Rng, RngCore, SeedableRng};
-
-const NUM_INPUTS: usize = 1024;
-const BENCH_SEED: u64 = 0xDEAD_BEEF_F000_BA55;
-
-fn sample_idx_and_mask64<R: Rng>(rng: &mut R) -> (u8, u64) {
-    let idx = rng.gen_range(0..MAX_PERIOD);
-    let mask_bits = rng.gen_range(idx + 1..=MAX_PERIOD);
-    let mut bit_scatter = BitScatter::new(rng, MAX_PERIOD);
-    let bits_perm = bit_scatter.iter().take(mask_bits as usize);
-    let mask = fill_mask(bits_perm).0;
-    (idx, mask)
-}
 
-fn pdep32_bench(c: &mut Criterion) {
-    let mut rng = SmallRng::seed_from_u64(BENCH_SEED);
+mod scenarios;
+
+use scenarios::{pdep32_inputs, select64_input_classes, select64_inputs, PDEP32_ONE_INPUT};
 
-    let inputs = (0..NUM_INPUTS)
-        .into_iter()
-        .map(|_| (rng.next_u32(), rng.next_u32()))
-        .collect::<Vec<_>>();
+fn pdep32_bench(c: &mut Criterion) {
+    let inputs = pdep32_inputs();
 
     let mut g = c.benchmark_group("pdep32");
     g.throughput(Throughput::Elements(inputs.len() as u64));
@@ -48,8 +35,7 @@ fn pdep32_bench(c: &mut Criterion) {
 }
 
 fn pdep32_bench_one_input(c: &mut Criterion) {
-    let src = 0x5555_3333_u32;
-    let mask = 0xF0F0_550F_u32;
+    let (src, mask) = PDEP32_ONE_INPUT;
 
     let mut g = c.benchmark_group("pdep32_one_input");
 
@@ -66,12 +52,7 @@ fn pdep32_bench_one_input(c: &mut Criterion) {
 }
 
 fn select64_bench(c: &mut Criterion) {
-    let mut rng = SmallRng::seed_from_u64(BENCH_SEED);
-
-    let inputs = (0..NUM_INPUTS)
-        .into_iter()
-        .map(|_| sample_idx_and_mask64(&mut rng))
-        .collect::<Vec<_>>();
+    let inputs = select64_inputs();
 
     let mut g = c.benchmark_group("select64");
     g.throughput(Throughput::Elements(inputs.len() as u64));
@@ -101,10 +82,56 @@ fn select64_bench(c: &mut Criterion) {
     g.finish();
 }
 
+// Bench every select implementation on `inputs`, with `param` identifying the
+// input class.
+fn bench_select_impls(g: &mut BenchmarkGroup<WallTime>, param: &str, inputs: &[(u8, u64)]) {
+    g.throughput(Throughput::Elements(inputs.len() as u64));
+    g.bench_with_input(BenchmarkId::new("select64", param), inputs, |b, inputs| {
+        b.iter(|| {
+            for &(idx, mask) in inputs {
+                black_box(select64(idx, mask));
+            }
+        })
+    });
+    g.bench_with_input(
+        BenchmarkId::new("select64_via_pdep32", param),
+        inputs,
+        |b, inputs| {
+            b.iter(|| {
+                for &(idx, mask) in inputs {
+                    black_box(select64_via_pdep32(idx, mask));
+                }
+            })
+        },
+    );
+    g.bench_with_input(
+        BenchmarkId::new("select64_fallback", param),
+        inputs,
+        |b, inputs| {
+            b.iter(|| {
+                for &(idx, mask) in inputs {
+                    black_box(select64_fallback(idx, mask));
+                }
+            })
+        },
+    );
+}
+
+fn select64_by_input_class_bench(c: &mut Criterion) {
+    for (group, classes) in select64_input_classes() {
+        let mut g = c.benchmark_group(group);
+        for (param, inputs) in classes {
+            bench_select_impls(&mut g, &param, &inputs);
+        }
+        g.finish();
+    }
+}
+
 criterion_group!(
     select64_benches,
     pdep32_bench,
     pdep32_bench_one_input,
-    select64_bench
+    select64_bench,
+    select64_by_input_class_bench
 );
 criterion_main!(select64_benches);
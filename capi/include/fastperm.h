@@ -0,0 +1,58 @@
+/*
+ * C API for fastperm, built with `cargo build --release -p fastperm-capi`.
+ *
+ * Link against `libfastperm_capi.so` or `libfastperm_capi.a` from
+ * `target/release`. These come from the `fastperm-capi` workspace crate in
+ * `capi/`, not from a feature of the `fastperm` crate. See `capi/src/lib.rs`
+ * for the Rust side of these declarations.
+ */
+
+#ifndef FASTPERM_H
+#define FASTPERM_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/*
+ * An opaque permuter that yields a random permutation of [0, n) one index at
+ * a time, then starts over with a fresh permutation.
+ *
+ * The output for a given seed and n is the same on every platform and in
+ * every release. A handle must not be used from several threads at once.
+ */
+typedef struct fastperm_bitscatter fastperm_bitscatter;
+
+/* Create a permuter over [0, n), or return NULL if n is not in [1, 64]. */
+fastperm_bitscatter *fastperm_bitscatter_new(uint64_t seed, uint8_t n);
+
+/* Return the next index in [0, n). */
+uint8_t fastperm_bitscatter_next(fastperm_bitscatter *perm);
+
+/* Abandon the current permutation, so the next call starts a fresh one. */
+void fastperm_bitscatter_reset(fastperm_bitscatter *perm);
+
+/*
+ * Write the next len indices into buf, exactly as len calls to
+ * fastperm_bitscatter_next would. Returns len.
+ */
+size_t fastperm_bitscatter_fill(fastperm_bitscatter *perm, uint8_t *buf, size_t len);
+
+/* Free a permuter. Does nothing if perm is NULL. */
+void fastperm_bitscatter_free(fastperm_bitscatter *perm);
+
+/*
+ * The position of the idx'th one bit in mask, counting from the least
+ * significant bit, e.g., fastperm_select64(1, 0x16) == 2. Returns 64 if mask
+ * has idx or fewer one bits.
+ */
+uint8_t fastperm_select64(uint8_t idx, uint64_t mask);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* FASTPERM_H */
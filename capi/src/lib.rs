@@ -0,0 +1,153 @@
+//! A C ABI for embedding [`BitScatter`] in C and C++ programs.
+//!
+//! `include/fastperm.h` declares these functions. Build the shared and static
+//! libraries with:
+//!
+//! ```text
+//! cargo build --release -p fastperm-capi
+//! ```
+//!
+//! which writes `libfastperm_capi.so` (or `.dylib`, `.dll`) and
+//! `libfastperm_capi.a` to `target/release`. `tests/capi.c` is a small C
+//! program using every function.
+//!
+//! There's no `capi` feature on `fastperm` itself: the C API is this separate
+//! `fastperm-capi` workspace crate, so only it is built as a `cdylib` and
+//! `staticlib`, rather than every build of `fastperm`.
+//!
+//! Permuters are seeded through a [`StableRng`], so a C caller gets exactly
+//! the same indices as `BitScatter::new(StableRng::seed_from_u64(seed), n)` in
+//! Rust, as pinned by the [`stable`](fastperm::stable) guarantees.
+
+use fastperm::{
+    select64::select64, stable::StableRng, BitScatter, SmallIndexPermutations, MAX_PERIOD,
+};
+use rand::SeedableRng;
+use std::ptr;
+
+/// The opaque `fastperm_bitscatter` handle.
+pub struct BitScatterHandle(BitScatter<StableRng>);
+
+/// Create a permuter over `[0, n)` seeded with `seed`. Returns null if `n` is
+/// not in `[1, 64]`. Free it with [`fastperm_bitscatter_free`].
+#[no_mangle]
+pub extern "C" fn fastperm_bitscatter_new(seed: u64, n: u8) -> *mut BitScatterHandle {
+    if n == 0 || n > MAX_PERIOD {
+        return ptr::null_mut();
+    }
+    let perm = BitScatter::new(StableRng::seed_from_u64(seed), n);
+    Box::into_raw(Box::new(BitScatterHandle(perm)))
+}
+
+/// Return the next index, starting a new period once the current one is
+/// exhausted.
+///
+/// # Safety
+///
+/// `perm` must be a live handle from [`fastperm_bitscatter_new`], not used
+/// concurrently from another thread.
+#[no_mangle]
+pub unsafe extern "C" fn fastperm_bitscatter_next(perm: *mut BitScatterHandle) -> u8 {
+    (*perm).0.next_index()
+}
+
+/// Abandon the current period, so the next call starts a fresh one.
+///
+/// # Safety
+///
+/// See [`fastperm_bitscatter_next`].
+#[no_mangle]
+pub unsafe extern "C" fn fastperm_bitscatter_reset(perm: *mut BitScatterHandle) {
+    (*perm).0.reset()
+}
+
+/// Write the next `len` indices into `buf`, exactly as `len` calls to
+/// [`fastperm_bitscatter_next`] would. Returns `len`.
+///
+/// # Safety
+///
+/// See [`fastperm_bitscatter_next`]. `buf` must be valid for writing `len`
+/// bytes, though it may be null if `len` is 0.
+#[no_mangle]
+pub unsafe extern "C" fn fastperm_bitscatter_fill(
+    perm: *mut BitScatterHandle,
+    buf: *mut u8,
+    len: usize,
+) -> usize {
+    if len == 0 {
+        return 0;
+    }
+    (*perm).0.fill(std::slice::from_raw_parts_mut(buf, len))
+}
+
+/// Free a permuter. Does nothing if `perm` is null.
+///
+/// # Safety
+///
+/// `perm` must be null or a handle from [`fastperm_bitscatter_new`] that
+/// hasn't already been freed.
+#[no_mangle]
+pub unsafe extern "C" fn fastperm_bitscatter_free(perm: *mut BitScatterHandle) {
+    if !perm.is_null() {
+        drop(Box::from_raw(perm));
+    }
+}
+
+/// The position of the `idx`'th one bit in `mask`, counting from the least
+/// significant bit. Returns 64 if `mask` has `idx` or fewer one bits.
+#[no_mangle]
+pub extern "C" fn fastperm_select64(idx: u8, mask: u64) -> u8 {
+    if u32::from(idx) >= mask.count_ones() {
+        return MAX_PERIOD;
+    }
+    select64(idx, mask)
+}
+
+//////////
+// Test //
+//////////
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_bitscatter_matches_rust() {
+        let mut expected = BitScatter::new(StableRng::seed_from_u64(7), 8);
+        let perm = fastperm_bitscatter_new(7, 8);
+        assert!(!perm.is_null());
+
+        unsafe {
+            for _ in 0..20 {
+                assert_eq!(expected.next_index(), fastperm_bitscatter_next(perm));
+            }
+
+            expected.reset();
+            fastperm_bitscatter_reset(perm);
+            let mut expected_buf = [0_u8; 13];
+            let mut buf = [0_u8; 13];
+            expected.fill(&mut expected_buf);
+            assert_eq!(13, fastperm_bitscatter_fill(perm, buf.as_mut_ptr(), 13));
+            assert_eq!(expected_buf, buf);
+            assert_eq!(0, fastperm_bitscatter_fill(perm, ptr::null_mut(), 0));
+
+            fastperm_bitscatter_free(perm);
+            fastperm_bitscatter_free(ptr::null_mut());
+        }
+
+        assert!(fastperm_bitscatter_new(0, 0).is_null());
+        assert!(fastperm_bitscatter_new(0, 65).is_null());
+        let perm = fastperm_bitscatter_new(0, 64);
+        assert!(!perm.is_null());
+        unsafe { fastperm_bitscatter_free(perm) };
+    }
+
+    #[test]
+    fn test_select64() {
+        assert_eq!(1, fastperm_select64(0, 0b10110));
+        assert_eq!(4, fastperm_select64(2, 0b10110));
+        assert_eq!(64, fastperm_select64(3, 0b10110));
+        assert_eq!(64, fastperm_select64(0, 0));
+        assert_eq!(63, fastperm_select64(63, u64::MAX));
+    }
+}
@@ -0,0 +1,72 @@
+/* Exercises the C API; built and run by `tests/capi.rs`. */
+
+#include <stdint.h>
+#include <stdio.h>
+
+#include "fastperm.h"
+
+#define CHECK(cond)                                                       \
+    do {                                                                  \
+        if (!(cond)) {                                                    \
+            fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__,        \
+                    __LINE__, #cond);                                     \
+            return 1;                                                     \
+        }                                                                 \
+    } while (0)
+
+int main(void) {
+    /* same as the `StableRng` doc example */
+    const uint8_t expected[8] = {0, 2, 6, 4, 7, 3, 5, 1};
+
+    fastperm_bitscatter *perm = fastperm_bitscatter_new(7, 8);
+    CHECK(perm != NULL);
+    for (int i = 0; i < 8; i++) {
+        CHECK(fastperm_bitscatter_next(perm) == expected[i]);
+    }
+
+    /* every period is a permutation */
+    for (int period = 0; period < 100; period++) {
+        uint64_t seen = 0;
+        for (int i = 0; i < 8; i++) {
+            uint8_t idx = fastperm_bitscatter_next(perm);
+            CHECK(idx < 8);
+            seen |= (uint64_t)1 << idx;
+        }
+        CHECK(seen == 0xff);
+    }
+    fastperm_bitscatter_free(perm);
+
+    /* fill matches next, across period boundaries */
+    fastperm_bitscatter *a = fastperm_bitscatter_new(42, 64);
+    fastperm_bitscatter *b = fastperm_bitscatter_new(42, 64);
+    CHECK(a != NULL && b != NULL);
+    uint8_t buf[100];
+    CHECK(fastperm_bitscatter_fill(a, buf, sizeof buf) == sizeof buf);
+    for (size_t i = 0; i < sizeof buf; i++) {
+        CHECK(buf[i] == fastperm_bitscatter_next(b));
+    }
+
+    /* reset starts a fresh period */
+    fastperm_bitscatter_next(a);
+    fastperm_bitscatter_reset(a);
+    uint64_t seen = 0;
+    for (int i = 0; i < 64; i++) {
+        seen |= (uint64_t)1 << fastperm_bitscatter_next(a);
+    }
+    CHECK(seen == UINT64_MAX);
+    fastperm_bitscatter_free(a);
+    fastperm_bitscatter_free(b);
+    fastperm_bitscatter_free(NULL);
+
+    CHECK(fastperm_bitscatter_new(0, 0) == NULL);
+    CHECK(fastperm_bitscatter_new(0, 65) == NULL);
+
+    CHECK(fastperm_select64(0, 0x16) == 1);
+    CHECK(fastperm_select64(1, 0x16) == 2);
+    CHECK(fastperm_select64(2, 0x16) == 4);
+    CHECK(fastperm_select64(3, 0x16) == 64);
+    CHECK(fastperm_select64(63, UINT64_MAX) == 63);
+
+    printf("ok\n");
+    return 0;
+}
@@ -0,0 +1,58 @@
+//! Compile `tests/capi.c` against `include/fastperm.h` and the `cdylib` built
+//! for this test run, then run it.
+//!
+//! ```text
+//! cargo test -p fastperm-capi --test capi
+//! ```
+//!
+//! Uses `$CC`, or `cc` if unset, and skips the test if that can't be run.
+
+#![cfg(unix)]
+
+use std::{
+    env,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+// Cargo leaves the library built for tests in `target/<profile>/deps`, next
+// to the test binary.
+fn lib_dir() -> PathBuf {
+    let exe = env::current_exe().unwrap();
+    exe.parent().unwrap().to_owned()
+}
+
+#[test]
+fn test_c_program() {
+    let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let lib_dir = lib_dir();
+    let exe = Path::new(env!("CARGO_TARGET_TMPDIR")).join("capi_test");
+    let cc = env::var("CC").unwrap_or_else(|_| "cc".to_owned());
+    if Command::new(&cc).arg("--version").output().is_err() {
+        eprintln!("skipping test_c_program: no C compiler `{}` found", cc);
+        return;
+    }
+
+    let status = Command::new(&cc)
+        .args(["-std=c99", "-Wall", "-Wextra", "-Werror"])
+        .arg("-I")
+        .arg(manifest_dir.join("include"))
+        .arg(manifest_dir.join("tests/capi.c"))
+        .arg("-o")
+        .arg(&exe)
+        .arg("-L")
+        .arg(&lib_dir)
+        .arg(format!("-Wl,-rpath,{}", lib_dir.display()))
+        .arg("-lfastperm_capi")
+        .status()
+        .unwrap_or_else(|err| panic!("failed to run {}: {}", cc, err));
+    assert!(status.success(), "failed to compile tests/capi.c");
+
+    let output = Command::new(&exe).output().unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!("ok\n", String::from_utf8_lossy(&output.stdout));
+}
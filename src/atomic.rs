@@ -0,0 +1,231 @@
+use crate::{fastmap32, index_mask, select64::select64, MAX_PERIOD};
+use rand::Rng;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// A [`BitScatter`](crate::BitScatter) that can be shared across threads
+/// without a lock.
+///
+/// The unchosen mask lives in an `AtomicU64`. Each call samples a bit from the
+/// currently loaded mask using the caller's own rng, then tries to clear it
+/// with a compare-and-swap, redrawing and retrying if another thread got there
+/// first. Every index is therefore handed out exactly once per period.
+///
+/// When the period is exhausted (the mask is empty), the next caller of
+/// [`next_index`](AtomicBitScatter::next_index) starts a new period: it
+/// samples from the full mask and swaps the empty mask for the full mask minus
+/// its pick. Exactly one thread's swap from the empty mask can succeed, so
+/// exactly one thread wins the refill; the others retry against the new period.
+/// [`try_next_index`](AtomicBitScatter::try_next_index) never refills and
+/// instead returns `None` once the period is exhausted.
+///
+/// ## Example
+///
+/// ```rust
+/// use fastperm::atomic::AtomicBitScatter;
+/// use rand::{rngs::SmallRng, SeedableRng};
+///
+/// let slots = AtomicBitScatter::new(16);
+/// let mask = std::thread::scope(|s| {
+///     let workers = (0..4)
+///         .map(|seed| {
+///             let slots = &slots;
+///             s.spawn(move || {
+///                 let mut rng = SmallRng::seed_from_u64(seed);
+///                 let mut mask = 0_u64;
+///                 while let Some(idx) = slots.try_next_index(&mut rng) {
+///                     mask |= 1 << idx;
+///                 }
+///                 mask
+///             })
+///         })
+///         .collect::<Vec<_>>();
+///     workers.into_iter().map(|w| w.join().unwrap()).fold(0, |a, b| a | b)
+/// });
+/// assert_eq!(0xFFFF, mask);
+/// ```
+pub struct AtomicBitScatter {
+    unchosen_mask: AtomicU64,
+    n: u8,
+}
+
+impl AtomicBitScatter {
+    pub fn new(n: u8) -> Self {
+        assert!(n <= MAX_PERIOD && n > 0);
+        Self {
+            unchosen_mask: AtomicU64::new(index_mask(n as u32)),
+            n,
+        }
+    }
+
+    #[inline(always)]
+    pub fn period(&self) -> u8 {
+        self.n
+    }
+
+    /// Number of indices left in the current period. Only a snapshot when
+    /// other threads are concurrently sampling.
+    #[inline]
+    pub fn remaining(&self) -> u8 {
+        self.unchosen_mask.load(Ordering::Acquire).count_ones() as u8
+    }
+
+    /// Start a new period, discarding any indices left in the current one.
+    #[inline]
+    pub fn reset(&self) {
+        self.unchosen_mask
+            .store(index_mask(self.n as u32), Ordering::Release);
+    }
+
+    /// Sample the next index, starting a new period if the current one is
+    /// exhausted.
+    #[inline]
+    pub fn next_index<R: Rng + ?Sized>(&self, rng: &mut R) -> u8 {
+        let full_mask = index_mask(self.n as u32);
+        let mut mask = self.unchosen_mask.load(Ordering::Acquire);
+        loop {
+            // sample from the next period if this one's empty
+            let from_mask = if mask == 0 { full_mask } else { mask };
+            let idx_nspace = sample(rng, from_mask);
+            let new_mask = from_mask & !(1_u64 << (idx_nspace as u32));
+
+            match self.unchosen_mask.compare_exchange_weak(
+                mask,
+                new_mask,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => return idx_nspace,
+                Err(actual) => mask = actual,
+            }
+        }
+    }
+
+    /// Sample the next index, or return `None` if the current period is
+    /// exhausted.
+    #[inline]
+    pub fn try_next_index<R: Rng + ?Sized>(&self, rng: &mut R) -> Option<u8> {
+        let mut mask = self.unchosen_mask.load(Ordering::Acquire);
+        loop {
+            if mask == 0 {
+                return None;
+            }
+            let idx_nspace = sample(rng, mask);
+            let new_mask = mask & !(1_u64 << (idx_nspace as u32));
+
+            match self.unchosen_mask.compare_exchange_weak(
+                mask,
+                new_mask,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => return Some(idx_nspace),
+                Err(actual) => mask = actual,
+            }
+        }
+    }
+}
+
+// Pick a uniformly random set bit in the (non-empty) `mask`.
+#[inline(always)]
+fn sample<R: Rng + ?Sized>(rng: &mut R, mask: u64) -> u8 {
+    let m = mask.count_ones();
+    let idx_mspace = fastmap32(rng.next_u32(), m) as u8;
+    select64(idx_mspace, mask)
+}
+
+//////////
+// Test //
+//////////
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::arb_small_rng;
+    use proptest::prelude::*;
+    use rand::{rngs::SmallRng, SeedableRng};
+
+    const NUM_THREADS: u64 = 8;
+
+    // Run `f(rng)` on `NUM_THREADS` threads at once and collect
+    // every thread's output.
+    fn run_threads<F>(f: F) -> Vec<u8>
+    where
+        F: Fn(&mut SmallRng) -> Vec<u8> + Sync,
+    {
+        let barrier = std::sync::Barrier::new(NUM_THREADS as usize);
+        std::thread::scope(|s| {
+            let handles = (0..NUM_THREADS)
+                .map(|thread_idx| {
+                    let (f, barrier) = (&f, &barrier);
+                    s.spawn(move || {
+                        let mut rng = SmallRng::seed_from_u64(thread_idx);
+                        barrier.wait();
+                        f(&mut rng)
+                    })
+                })
+                .collect::<Vec<_>>();
+            handles
+                .into_iter()
+                .flat_map(|handle| handle.join().unwrap())
+                .collect()
+        })
+    }
+
+    fn counts(n: u8, idxs: &[u8]) -> Vec<usize> {
+        let mut counts = vec![0; n as usize];
+        for &idx in idxs {
+            counts[idx as usize] += 1;
+        }
+        counts
+    }
+
+    #[test]
+    fn test_try_next_index_stress() {
+        for n in [1, 2, 7, 63, MAX_PERIOD] {
+            let atomic = AtomicBitScatter::new(n);
+            for _ in 0..200 {
+                atomic.reset();
+                let idxs =
+                    run_threads(|rng| std::iter::from_fn(|| atomic.try_next_index(rng)).collect());
+                // every index exactly once in the period
+                assert_eq!(vec![1; n as usize], counts(n, &idxs));
+                assert_eq!(0, atomic.remaining());
+            }
+        }
+    }
+
+    #[test]
+    fn test_next_index_refill_stress() {
+        const NUM_PERIODS: usize = 1000;
+        for n in [1, 5, 32, MAX_PERIOD] {
+            let atomic = AtomicBitScatter::new(n);
+            let per_thread = NUM_PERIODS * n as usize / NUM_THREADS as usize;
+            let idxs = run_threads(|rng| (0..per_thread).map(|_| atomic.next_index(rng)).collect());
+            // the threads together consume exactly NUM_PERIODS whole periods,
+            // so each index is handed out once per period and the last period
+            // ends exhausted.
+            assert_eq!(vec![NUM_PERIODS; n as usize], counts(n, &idxs));
+            assert_eq!(0, atomic.remaining());
+        }
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(1000))]
+
+        #[test]
+        fn atomic_bit_scatter_is_permutation(n in 1..=MAX_PERIOD, mut rng in arb_small_rng()) {
+            let atomic = AtomicBitScatter::new(n);
+            for _ in 0..2 {
+                let mut mask = 0_u64;
+                for remaining in (0..n).rev() {
+                    let idx = atomic.next_index(&mut rng);
+                    assert_eq!(0, mask & (1 << idx));
+                    mask |= 1 << idx;
+                    assert_eq!(remaining, atomic.remaining());
+                }
+                assert_eq!(index_mask(n as u32), mask);
+                assert_eq!(None, atomic.try_next_index(&mut rng));
+            }
+        }
+    }
+}
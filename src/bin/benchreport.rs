@@ -0,0 +1,586 @@
+//! Turn criterion's raw output into machine-readable results files, and render
+//! Markdown reports from them.
+//!
+//! ```text
+//! # collect `target/criterion` into a results file tagged with this machine
+//! benchreport collect [--csv] [CRITERION_DIR] > results.json
+//!
+//! # compare the algorithms in each benchmark group
+//! benchreport table results.json
+//!
+//! # compare two results files, e.g., before and after a change
+//! benchreport compare base.json new.json [--threshold PERCENT]
+//! ```
+//!
+//! `compare` marks any benchmark that got more than `--threshold` percent
+//! (default 5%) slower as a regression, and exits with status 1 if there are
+//! any.
+//!
+//! Build with `cargo run --features benchreport --bin benchreport -- ...`.
+
+use serde::{Deserialize, Serialize};
+use std::{
+    cmp::Ordering,
+    collections::BTreeMap,
+    error::Error,
+    fmt::Write as _,
+    fs,
+    path::{Path, PathBuf},
+    process,
+};
+
+type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+const USAGE: &str = "\
+usage: benchreport collect [--csv] [CRITERION_DIR]
+       benchreport table RESULTS_JSON
+       benchreport compare BASE_JSON NEW_JSON [--threshold PERCENT]";
+
+const DEFAULT_CRITERION_DIR: &str = "target/criterion";
+const DEFAULT_THRESHOLD_PCT: f64 = 5.0;
+
+/// A normalised results file: every benchmark estimate, tagged with the
+/// machine it ran on.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+struct Results {
+    machine: Machine,
+    benchmarks: Vec<Benchmark>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+struct Machine {
+    cpu: String,
+    arch: String,
+    /// The CPU features the permuters care about that this machine has, e.g.,
+    /// `bmi2` for `pdep`.
+    features: Vec<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+struct Benchmark {
+    group: String,
+    function: Option<String>,
+    param: Option<String>,
+    mean_ns: f64,
+    median_ns: f64,
+    std_err_ns: f64,
+    /// The number of elements each iteration processes, if the benchmark set
+    /// a throughput.
+    elements: Option<u64>,
+}
+
+impl Benchmark {
+    fn id(&self) -> String {
+        let mut id = self.group.clone();
+        for part in [&self.function, &self.param].into_iter().flatten() {
+            id.push('/');
+            id.push_str(part);
+        }
+        id
+    }
+
+    /// Mean time per element, or per iteration without a throughput.
+    fn ns_per_elem(&self) -> f64 {
+        match self.elements {
+            Some(elements) if elements > 0 => self.mean_ns / elements as f64,
+            _ => self.mean_ns,
+        }
+    }
+}
+
+// Criterion's `new/benchmark.json`.
+#[derive(Deserialize)]
+struct CriterionBenchmark {
+    group_id: String,
+    function_id: Option<String>,
+    value_str: Option<String>,
+    // e.g. `{"Elements": 64}` or `{"Bytes": 512}`
+    throughput: Option<BTreeMap<String, u64>>,
+}
+
+// Criterion's `new/estimates.json`.
+#[derive(Deserialize)]
+struct CriterionEstimates {
+    mean: CriterionEstimate,
+    median: CriterionEstimate,
+}
+
+#[derive(Deserialize)]
+struct CriterionEstimate {
+    point_estimate: f64,
+    standard_error: f64,
+}
+
+fn main() {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    match run(&args) {
+        Ok(status) => process::exit(status),
+        Err(err) => {
+            eprintln!("error: {}\n\n{}", err, USAGE);
+            process::exit(2);
+        }
+    }
+}
+
+fn run(args: &[String]) -> Result<i32> {
+    let (cmd, args) = args.split_first().ok_or("missing command")?;
+    match cmd.as_str() {
+        "collect" => {
+            let csv = args.iter().any(|arg| arg == "--csv");
+            let dir = match args.iter().find(|arg| !arg.starts_with("--")) {
+                Some(dir) => PathBuf::from(dir),
+                None => PathBuf::from(DEFAULT_CRITERION_DIR),
+            };
+            let results = Results {
+                machine: Machine::current(),
+                benchmarks: collect(&dir)?,
+            };
+            if csv {
+                print!("{}", to_csv(&results));
+            } else {
+                println!("{}", serde_json::to_string_pretty(&results)?);
+            }
+            Ok(0)
+        }
+        "table" => {
+            let [path] = args else {
+                return Err("table takes one results file".into());
+            };
+            print!("{}", render_table(&read_results(path)?));
+            Ok(0)
+        }
+        "compare" => {
+            let (paths, threshold_pct) = match args {
+                [base, new] => ([base, new], DEFAULT_THRESHOLD_PCT),
+                [base, new, flag, pct] if flag == "--threshold" => ([base, new], pct.parse()?),
+                _ => return Err("compare takes two results files".into()),
+            };
+            let base = read_results(paths[0])?;
+            let new = read_results(paths[1])?;
+            let (report, num_regressions) = render_compare(&base, &new, threshold_pct);
+            print!("{}", report);
+            Ok(if num_regressions > 0 { 1 } else { 0 })
+        }
+        _ => Err(format!("unknown command: {}", cmd).into()),
+    }
+}
+
+fn read_results(path: impl AsRef<Path>) -> Result<Results> {
+    let path = path.as_ref();
+    let bytes = fs::read(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+    Ok(serde_json::from_slice(&bytes)?)
+}
+
+/////////////
+// Collect //
+/////////////
+
+impl Machine {
+    fn current() -> Self {
+        Self {
+            cpu: cpu_model().unwrap_or_else(|| "unknown".to_owned()),
+            arch: std::env::consts::ARCH.to_owned(),
+            features: cpu_features(),
+        }
+    }
+}
+
+fn cpu_model() -> Option<String> {
+    let cpuinfo = fs::read_to_string("/proc/cpuinfo").ok()?;
+    cpuinfo
+        .lines()
+        .find(|line| line.starts_with("model name"))
+        .and_then(|line| line.split_once(':'))
+        .map(|(_, model)| model.trim().to_owned())
+}
+
+fn cpu_features() -> Vec<String> {
+    let mut features = Vec::new();
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    {
+        macro_rules! detect {
+            ($($feature:tt),*) => {
+                $(
+                    if is_x86_feature_detected!($feature) {
+                        features.push($feature.to_owned());
+                    }
+                )*
+            };
+        }
+        detect!("popcnt", "bmi1", "bmi2", "avx2", "avx512f");
+    }
+    features
+}
+
+/// Collect the latest estimate of every benchmark under `dir`, i.e.,
+/// criterion's `*/new/{benchmark,estimates}.json` files.
+fn collect(dir: &Path) -> Result<Vec<Benchmark>> {
+    let mut new_dirs = Vec::new();
+    find_new_dirs(dir, &mut new_dirs).map_err(|err| format!("{}: {}", dir.display(), err))?;
+
+    let mut benchmarks = new_dirs
+        .iter()
+        .map(|dir| read_criterion_benchmark(dir))
+        .collect::<Result<Vec<_>>>()?;
+    benchmarks.sort_by(cmp_benchmarks);
+    Ok(benchmarks)
+}
+
+fn find_new_dirs(dir: &Path, new_dirs: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if !path.is_dir() {
+            continue;
+        }
+        if path.file_name() == Some("new".as_ref()) && path.join("benchmark.json").is_file() {
+            new_dirs.push(path);
+        } else {
+            find_new_dirs(&path, new_dirs)?;
+        }
+    }
+    Ok(())
+}
+
+fn read_criterion_benchmark(new_dir: &Path) -> Result<Benchmark> {
+    let read = |name: &str| {
+        let path = new_dir.join(name);
+        fs::read(&path).map_err(|err| format!("{}: {}", path.display(), err))
+    };
+    let benchmark: CriterionBenchmark = serde_json::from_slice(&read("benchmark.json")?)?;
+    let estimates: CriterionEstimates = serde_json::from_slice(&read("estimates.json")?)?;
+
+    Ok(Benchmark {
+        group: benchmark.group_id,
+        function: benchmark.function_id,
+        param: benchmark.value_str,
+        mean_ns: estimates.mean.point_estimate,
+        median_ns: estimates.median.point_estimate,
+        std_err_ns: estimates.mean.standard_error,
+        elements: benchmark
+            .throughput
+            .and_then(|throughput| throughput.get("Elements").copied()),
+    })
+}
+
+// Sort by group, then function, then param, comparing numeric params by value
+// so `n = 8` comes before `n = 16`.
+fn cmp_benchmarks(a: &Benchmark, b: &Benchmark) -> Ordering {
+    (&a.group, &a.function)
+        .cmp(&(&b.group, &b.function))
+        .then_with(|| cmp_params(a.param.as_deref(), b.param.as_deref()))
+}
+
+fn cmp_params(a: Option<&str>, b: Option<&str>) -> Ordering {
+    fn key(param: Option<&str>) -> Option<(Option<u64>, &str)> {
+        param.map(|param| (param.parse().ok(), param))
+    }
+    key(a).cmp(&key(b))
+}
+
+fn to_csv(results: &Results) -> String {
+    let machine = &results.machine;
+    let mut out = String::from(
+        "cpu,arch,features,group,function,param,mean_ns,median_ns,std_err_ns,elements\n",
+    );
+    for bench in &results.benchmarks {
+        let fields = [
+            csv_field(&machine.cpu),
+            csv_field(&machine.arch),
+            csv_field(&machine.features.join("+")),
+            csv_field(&bench.group),
+            csv_field(bench.function.as_deref().unwrap_or("")),
+            csv_field(bench.param.as_deref().unwrap_or("")),
+            bench.mean_ns.to_string(),
+            bench.median_ns.to_string(),
+            bench.std_err_ns.to_string(),
+            bench.elements.map(|n| n.to_string()).unwrap_or_default(),
+        ];
+        out.push_str(&fields.join(","));
+        out.push('\n');
+    }
+    out
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
+
+////////////
+// Render //
+////////////
+
+fn render_machine(out: &mut String, machine: &Machine) {
+    writeln!(
+        out,
+        "_{} ({}), features: {}_\n",
+        machine.cpu,
+        machine.arch,
+        if machine.features.is_empty() {
+            "none".to_owned()
+        } else {
+            machine.features.join(", ")
+        },
+    )
+    .unwrap();
+}
+
+/// One table per benchmark group, with a row per function and a column per
+/// param, so crossovers between algorithms stand out. The fastest function
+/// for each param is in bold.
+fn render_table(results: &Results) -> String {
+    let mut out = String::new();
+    render_machine(&mut out, &results.machine);
+
+    let mut groups = BTreeMap::<&str, Vec<&Benchmark>>::new();
+    for bench in &results.benchmarks {
+        groups.entry(&bench.group).or_default().push(bench);
+    }
+
+    for (group, benches) in groups {
+        let unit = if benches.iter().all(|bench| bench.elements.is_some()) {
+            "ns/elem"
+        } else {
+            "ns/iter"
+        };
+        let mut params = benches
+            .iter()
+            .map(|bench| bench.param.as_deref())
+            .collect::<Vec<_>>();
+        params.sort_by(|a, b| cmp_params(*a, *b));
+        params.dedup();
+        let mut functions = benches
+            .iter()
+            .map(|bench| bench.function.as_deref())
+            .collect::<Vec<_>>();
+        functions.sort_unstable();
+        functions.dedup();
+
+        let lookup = |function: Option<&str>, param: Option<&str>| {
+            benches
+                .iter()
+                .find(|bench| {
+                    bench.function.as_deref() == function && bench.param.as_deref() == param
+                })
+                .map(|bench| bench.ns_per_elem())
+        };
+        let fastest = params
+            .iter()
+            .map(|&param| {
+                functions
+                    .iter()
+                    .filter_map(|&function| lookup(function, param))
+                    .fold(f64::INFINITY, f64::min)
+            })
+            .collect::<Vec<_>>();
+
+        writeln!(out, "### {} ({})\n", group, unit).unwrap();
+        let header = params
+            .iter()
+            .map(|param| param.unwrap_or("-"))
+            .collect::<Vec<_>>();
+        writeln!(out, "| | {} |", header.join(" | ")).unwrap();
+        writeln!(out, "|---|{}", "---:|".repeat(params.len())).unwrap();
+        for &function in &functions {
+            let cells = params
+                .iter()
+                .zip(&fastest)
+                .map(|(&param, &fastest)| match lookup(function, param) {
+                    Some(ns) if ns == fastest => format!("**{:.2}**", ns),
+                    Some(ns) => format!("{:.2}", ns),
+                    None => String::new(),
+                })
+                .collect::<Vec<_>>();
+            writeln!(
+                out,
+                "| {} | {} |",
+                function.unwrap_or("-"),
+                cells.join(" | ")
+            )
+            .unwrap();
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// A table of every benchmark in both files, with the relative change in mean
+/// time. Returns the report and the number of regressions, i.e., benchmarks
+/// that got more than `threshold_pct` percent slower.
+fn render_compare(base: &Results, new: &Results, threshold_pct: f64) -> (String, usize) {
+    let mut out = String::new();
+    out.push_str("base: ");
+    render_machine(&mut out, &base.machine);
+    out.push_str("new: ");
+    render_machine(&mut out, &new.machine);
+    if base.machine != new.machine {
+        out.push_str("**warning: the results are from different machines**\n\n");
+    }
+
+    let base_by_id = base
+        .benchmarks
+        .iter()
+        .map(|bench| (bench.id(), bench))
+        .collect::<BTreeMap<_, _>>();
+
+    let mut num_regressions = 0;
+    let mut only_new = Vec::new();
+    out.push_str("| benchmark | base (ns) | new (ns) | change |\n");
+    out.push_str("|---|---:|---:|---:|\n");
+    for new_bench in &new.benchmarks {
+        let id = new_bench.id();
+        let Some(base_bench) = base_by_id.get(&id) else {
+            only_new.push(id);
+            continue;
+        };
+        let change_pct = (new_bench.mean_ns / base_bench.mean_ns - 1.0) * 100.0;
+        let change = if change_pct > threshold_pct {
+            num_regressions += 1;
+            format!("**{:+.1}% regressed**", change_pct)
+        } else if change_pct < -threshold_pct {
+            format!("{:+.1}% improved", change_pct)
+        } else {
+            format!("{:+.1}%", change_pct)
+        };
+        writeln!(
+            out,
+            "| {} | {:.2} | {:.2} | {} |",
+            id, base_bench.mean_ns, new_bench.mean_ns, change
+        )
+        .unwrap();
+    }
+
+    let new_ids = new.benchmarks.iter().map(Benchmark::id).collect::<Vec<_>>();
+    let only_base = base_by_id
+        .keys()
+        .filter(|id| !new_ids.contains(id))
+        .map(String::as_str)
+        .collect::<Vec<_>>();
+    let only_new = only_new.iter().map(String::as_str).collect::<Vec<_>>();
+    for (label, ids) in [("base", only_base), ("new", only_new)] {
+        if !ids.is_empty() {
+            writeln!(out, "\nonly in {}: {}", label, ids.join(", ")).unwrap();
+        }
+    }
+
+    writeln!(
+        out,
+        "\n{} regression(s) over {}%",
+        num_regressions, threshold_pct
+    )
+    .unwrap();
+    (out, num_regressions)
+}
+
+//////////
+// Test //
+//////////
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn bench(function: &str, param: &str, mean_ns: f64) -> Benchmark {
+        Benchmark {
+            group: "group".to_owned(),
+            function: Some(function.to_owned()),
+            param: Some(param.to_owned()),
+            mean_ns,
+            median_ns: mean_ns,
+            std_err_ns: 0.0,
+            elements: Some(param.parse().unwrap()),
+        }
+    }
+
+    fn results(benchmarks: Vec<Benchmark>) -> Results {
+        Results {
+            machine: Machine {
+                cpu: "Test CPU".to_owned(),
+                arch: "x86_64".to_owned(),
+                features: vec!["bmi2".to_owned()],
+            },
+            benchmarks,
+        }
+    }
+
+    #[test]
+    fn test_collect_criterion_dir() {
+        let dir = std::env::temp_dir().join(format!("benchreport-test-{}", process::id()));
+        for (function, value, mean) in [("b", "16", 32.0), ("b", "8", 8.0), ("a", "8", 4.0)] {
+            let new_dir = dir.join("group").join(function).join(value).join("new");
+            fs::create_dir_all(&new_dir).unwrap();
+            let benchmark = format!(
+                r#"{{"group_id":"group","function_id":"{}","value_str":"{}","throughput":{{"Elements":{}}},"full_id":"","directory_name":"","title":""}}"#,
+                function, value, value,
+            );
+            let estimate = format!(
+                r#"{{"confidence_interval":{{"confidence_level":0.95,"lower_bound":0.0,"upper_bound":0.0}},"point_estimate":{},"standard_error":0.5}}"#,
+                mean,
+            );
+            let estimates = format!(r#"{{"mean":{},"median":{}}}"#, estimate, estimate);
+            fs::write(new_dir.join("benchmark.json"), benchmark).unwrap();
+            fs::write(new_dir.join("estimates.json"), estimates).unwrap();
+        }
+
+        let benchmarks = collect(&dir).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        let ids = benchmarks.iter().map(Benchmark::id).collect::<Vec<_>>();
+        assert_eq!(vec!["group/a/8", "group/b/8", "group/b/16"], ids);
+        assert_eq!(2.0, benchmarks[2].ns_per_elem());
+        assert_eq!(0.5, benchmarks[2].std_err_ns);
+    }
+
+    #[test]
+    fn test_render_table_marks_fastest() {
+        let results = results(vec![
+            bench("bit_scatter", "8", 16.0),
+            bench("bit_scatter", "64", 64.0),
+            bench("shuffle", "8", 8.0),
+            bench("shuffle", "64", 128.0),
+        ]);
+        let table = render_table(&results);
+        assert!(table.contains("### group (ns/elem)"));
+        assert!(table.contains("| | 8 | 64 |"));
+        assert!(table.contains("| bit_scatter | 2.00 | **1.00** |"));
+        assert!(table.contains("| shuffle | **1.00** | 2.00 |"));
+    }
+
+    #[test]
+    fn test_render_compare_regressions() {
+        let base = results(vec![
+            bench("a", "8", 10.0),
+            bench("b", "8", 10.0),
+            bench("c", "8", 10.0),
+            bench("d", "8", 10.0),
+        ]);
+        let new = results(vec![
+            bench("a", "8", 10.4),
+            bench("b", "8", 12.0),
+            bench("c", "8", 5.0),
+            bench("e", "8", 10.0),
+        ]);
+        let (report, num_regressions) = render_compare(&base, &new, 5.0);
+        assert_eq!(1, num_regressions);
+        assert!(report.contains("| group/a/8 | 10.00 | 10.40 | +4.0% |"));
+        assert!(report.contains("| group/b/8 | 10.00 | 12.00 | **+20.0% regressed** |"));
+        assert!(report.contains("| group/c/8 | 10.00 | 5.00 | -50.0% improved |"));
+        assert!(report.contains("only in base: group/d/8"));
+        assert!(report.contains("only in new: group/e/8"));
+    }
+
+    #[test]
+    fn test_csv_quoting() {
+        let mut results = results(vec![bench("a", "8", 10.0)]);
+        results.machine.cpu = "Test CPU, \"Fast\"".to_owned();
+        let csv = to_csv(&results);
+        let row = csv.lines().nth(1).unwrap();
+        assert_eq!(
+            r#""Test CPU, ""Fast""",x86_64,bmi2,group,a,8,10,10,0,8"#,
+            row
+        );
+    }
+}
@@ -0,0 +1,608 @@
+//! Generate random permutations of `[0, n)` from the command line, e.g., a
+//! shuffled host order or a random canary subset, with any of the permuters.
+//!
+//! ```text
+//! $ fastperm -n 10 --seed 7
+//! 0 2 7 4 9 5 6 1 8 3
+//! $ fastperm -n 40 -k 3 --periods 2 --format csv
+//! ```
+//!
+//! Output for a given `--seed` is reproducible, since the rng is a
+//! [`StableRng`](fastperm::stable::StableRng).
+//!
+//! `fastperm stats` instead runs a quick statistical self-test of a permuter;
+//! see the [`stats`] module.
+
+use fastperm::{
+    lehmer::{LehmerPermuter, MAX_LEHMER_PERIOD},
+    stable::StableRng,
+    weighted::WeightedPermuter,
+    AutoPermuter, BitScatter, Derangement, NoRepeat, Sattolo, Shuffle, ShuffleArray,
+    ShuffleArrayIncremental, ShuffleBag, SmallIndexPermutations, MAX_PERIOD,
+};
+use rand::SeedableRng;
+use std::{
+    io::{self, Write},
+    process,
+    str::FromStr,
+};
+
+mod stats;
+
+const USAGE: &str = "\
+usage: fastperm [OPTIONS]
+       fastperm stats [OPTIONS]
+
+Generate random permutations of [0, n), one period per line.
+
+With `stats`, test that the permuter's periods look like independent, uniform
+permutations instead, over many periods (default: 1000000), and print a
+pass/fail verdict for each test. Needs at least 2 periods, and doesn't take
+-k, --format or shuffle-bag.
+
+options:
+  -a, --algo ALGO       the permuter (default: bit-scatter), one of:
+                        shuffle, shuffle-array, shuffle-array-incr,
+                        bit-scatter, sattolo, derangement, lehmer, no-repeat,
+                        auto, shuffle-bag, weighted
+  -n N                  the period, in 1..=64 (default: 10)
+  -p, --periods P       the number of periods to output (default: 1)
+  -k K                  only output the first K indices of each period, i.e.,
+                        a random K-subset in random order
+  -s, --seed SEED       seed for reproducible output, decimal or 0x hex
+                        (default: random, printed to stderr)
+  -f, --format FORMAT   plain, csv or json (default: plain)
+      --counts C,...    each item's count for shuffle-bag; n is their sum
+      --weights W,...   each index's weight for weighted; n is their number
+  -h, --help            print this help";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Algo {
+    Shuffle,
+    ShuffleArray,
+    ShuffleArrayIncr,
+    BitScatter,
+    Sattolo,
+    Derangement,
+    Lehmer,
+    NoRepeat,
+    Auto,
+    ShuffleBag,
+    Weighted,
+}
+
+impl Algo {
+    const ALL: [(&'static str, Algo); 11] = [
+        ("shuffle", Algo::Shuffle),
+        ("shuffle-array", Algo::ShuffleArray),
+        ("shuffle-array-incr", Algo::ShuffleArrayIncr),
+        ("bit-scatter", Algo::BitScatter),
+        ("sattolo", Algo::Sattolo),
+        ("derangement", Algo::Derangement),
+        ("lehmer", Algo::Lehmer),
+        ("no-repeat", Algo::NoRepeat),
+        ("auto", Algo::Auto),
+        ("shuffle-bag", Algo::ShuffleBag),
+        ("weighted", Algo::Weighted),
+    ];
+
+    fn name(self) -> &'static str {
+        Self::ALL
+            .iter()
+            .find(|(_, algo)| *algo == self)
+            .map(|(name, _)| *name)
+            .unwrap()
+    }
+}
+
+impl FromStr for Algo {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .iter()
+            .find(|(name, _)| *name == s)
+            .map(|(_, algo)| *algo)
+            .ok_or_else(|| format!("unknown algo: {}", s))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Command {
+    Generate,
+    Stats,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Format {
+    Plain,
+    Csv,
+    Json,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "plain" => Ok(Self::Plain),
+            "csv" => Ok(Self::Csv),
+            "json" => Ok(Self::Json),
+            _ => Err(format!("unknown format: {}", s)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Options {
+    command: Command,
+    algo: Algo,
+    n: u8,
+    periods: Option<u64>,
+    k: Option<u8>,
+    seed: Option<u64>,
+    format: Format,
+    counts: Vec<u8>,
+    weights: Vec<f64>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            command: Command::Generate,
+            algo: Algo::BitScatter,
+            n: 10,
+            periods: None,
+            k: None,
+            seed: None,
+            format: Format::Plain,
+            counts: Vec::new(),
+            weights: Vec::new(),
+        }
+    }
+}
+
+impl Options {
+    /// Parse the command line args, or `Ok(None)` if the user asked for help.
+    fn parse(args: &[String]) -> Result<Option<Self>, String> {
+        let mut opts = Self::default();
+        let args = match args.split_first() {
+            Some((cmd, args)) if cmd == "stats" => {
+                opts.command = Command::Stats;
+                args
+            }
+            _ => args,
+        };
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| format!("missing value for {}", arg))
+            };
+            match arg.as_str() {
+                "-h" | "--help" => return Ok(None),
+                "-a" | "--algo" => opts.algo = value()?.parse()?,
+                "-n" => opts.n = parse_num(value()?)?,
+                "-p" | "--periods" => opts.periods = Some(parse_num(value()?)?),
+                "-k" => opts.k = Some(parse_num(value()?)?),
+                "-s" | "--seed" => opts.seed = Some(parse_num(value()?)?),
+                "-f" | "--format" => opts.format = value()?.parse()?,
+                "--counts" => opts.counts = parse_list(value()?)?,
+                "--weights" => opts.weights = parse_list(value()?)?,
+                _ => return Err(format!("unknown argument: {}", arg)),
+            }
+        }
+
+        // the bag and weights determine the period themselves
+        match opts.algo {
+            Algo::ShuffleBag => {
+                let total = opts.counts.iter().map(|&count| count as u32).sum::<u32>();
+                if total == 0 || total > MAX_PERIOD as u32 {
+                    return Err(format!(
+                        "shuffle-bag needs --counts summing to 1..={}",
+                        MAX_PERIOD
+                    ));
+                }
+                opts.n = total as u8;
+            }
+            Algo::Weighted => {
+                let valid = opts.weights.iter().all(|w| w.is_finite() && *w >= 0.0)
+                    && opts.weights.iter().sum::<f64>().is_finite();
+                if opts.weights.is_empty() || opts.weights.len() > MAX_PERIOD as usize || !valid {
+                    return Err(format!(
+                        "weighted needs 1..={} finite, non-negative --weights with a finite sum",
+                        MAX_PERIOD
+                    ));
+                }
+                opts.n = opts.weights.len() as u8;
+            }
+            _ => {}
+        }
+
+        if opts.n == 0 || opts.n > MAX_PERIOD {
+            return Err(format!("n must be in 1..={}", MAX_PERIOD));
+        }
+        if opts.k.is_some_and(|k| k > opts.n) {
+            return Err("k must be at most n".to_owned());
+        }
+        let min_n = match opts.algo {
+            Algo::Derangement | Algo::NoRepeat => 2,
+            _ => 1,
+        };
+        if opts.n < min_n {
+            return Err(format!("{} needs n >= {}", opts.algo.name(), min_n));
+        }
+        if opts.algo == Algo::Lehmer && opts.n > MAX_LEHMER_PERIOD {
+            return Err(format!("lehmer needs n <= {}", MAX_LEHMER_PERIOD));
+        }
+        if opts.command == Command::Stats {
+            if opts.k.is_some() || opts.format != Format::Plain {
+                return Err("stats doesn't take -k or --format".to_owned());
+            }
+            if opts.n < 2 {
+                return Err("stats needs n >= 2".to_owned());
+            }
+            if opts.periods.is_some_and(|periods| periods < 2) {
+                return Err("stats needs --periods >= 2".to_owned());
+            }
+            // its periods aren't permutations, so the tests would index past n
+            if opts.algo == Algo::ShuffleBag {
+                return Err("stats needs a permuter, not shuffle-bag".to_owned());
+            }
+        }
+        Ok(Some(opts))
+    }
+
+    fn num_periods(&self) -> u64 {
+        let default = match self.command {
+            Command::Generate => 1,
+            Command::Stats => stats::DEFAULT_NUM_PERIODS,
+        };
+        self.periods.unwrap_or(default)
+    }
+
+    fn num_idxs_per_period(&self) -> u8 {
+        self.k.unwrap_or(self.n)
+    }
+
+    fn new_source(&self, seed: u64) -> Source {
+        let rng = StableRng::seed_from_u64(seed);
+        let n = self.n;
+        let perm = match self.algo {
+            Algo::Shuffle => Permuter::Shuffle(Shuffle::new(rng, n)),
+            Algo::ShuffleArray => Permuter::ShuffleArray(ShuffleArray::new(rng, n)),
+            Algo::ShuffleArrayIncr => {
+                Permuter::ShuffleArrayIncr(ShuffleArrayIncremental::new(rng, n))
+            }
+            Algo::BitScatter => Permuter::BitScatter(BitScatter::new(rng, n)),
+            Algo::Sattolo => Permuter::Sattolo(Sattolo::new(rng, n)),
+            Algo::Derangement => Permuter::Derangement(Derangement::new(rng, n)),
+            Algo::Lehmer => Permuter::Lehmer(LehmerPermuter::new(rng, n)),
+            Algo::NoRepeat => {
+                Permuter::NoRepeat(NoRepeat::new(ShuffleArrayIncremental::new(rng, n)))
+            }
+            Algo::Auto => Permuter::Auto(AutoPermuter::new(rng, n)),
+            Algo::ShuffleBag => return Source::ShuffleBag(ShuffleBag::new(rng, &self.counts)),
+            Algo::Weighted => {
+                Permuter::Weighted(Box::new(WeightedPermuter::new(rng, &self.weights)))
+            }
+        };
+        Source::Permuter(perm)
+    }
+}
+
+/// What the output comes from: a permuter, or a shuffle bag, whose periods
+/// repeat items and so aren't permutations.
+enum Source {
+    Permuter(Permuter),
+    ShuffleBag(ShuffleBag<StableRng>),
+}
+
+impl Source {
+    fn reset(&mut self) {
+        match self {
+            Source::Permuter(perm) => perm.reset(),
+            Source::ShuffleBag(bag) => bag.reset(),
+        }
+    }
+
+    fn next_item(&mut self) -> u8 {
+        match self {
+            Source::Permuter(perm) => perm.next_index(),
+            Source::ShuffleBag(bag) => bag.next_item(),
+        }
+    }
+}
+
+/// Any of the permuters, chosen at runtime. An enum since
+/// `SmallIndexPermutations` isn't object safe.
+enum Permuter {
+    Shuffle(Shuffle<StableRng>),
+    ShuffleArray(ShuffleArray<StableRng>),
+    ShuffleArrayIncr(ShuffleArrayIncremental<StableRng>),
+    BitScatter(BitScatter<StableRng>),
+    Sattolo(Sattolo<StableRng>),
+    Derangement(Derangement<StableRng>),
+    Lehmer(LehmerPermuter<StableRng>),
+    NoRepeat(NoRepeat<ShuffleArrayIncremental<StableRng>>),
+    Auto(AutoPermuter<StableRng>),
+    // boxed, since it holds 64 `f64` weights
+    Weighted(Box<WeightedPermuter<StableRng>>),
+}
+
+macro_rules! dispatch {
+    ($self:expr, |$perm:ident| $body:expr) => {
+        match $self {
+            Permuter::Shuffle($perm) => $body,
+            Permuter::ShuffleArray($perm) => $body,
+            Permuter::ShuffleArrayIncr($perm) => $body,
+            Permuter::BitScatter($perm) => $body,
+            Permuter::Sattolo($perm) => $body,
+            Permuter::Derangement($perm) => $body,
+            Permuter::Lehmer($perm) => $body,
+            Permuter::NoRepeat($perm) => $body,
+            Permuter::Auto($perm) => $body,
+            Permuter::Weighted($perm) => $body,
+        }
+    };
+}
+
+impl SmallIndexPermutations for Permuter {
+    fn period(&self) -> u8 {
+        dispatch!(self, |p| p.period())
+    }
+
+    fn next_index(&mut self) -> u8 {
+        dispatch!(self, |p| p.next_index())
+    }
+
+    fn reset(&mut self) {
+        dispatch!(self, |p| p.reset())
+    }
+
+    fn fill(&mut self, buf: &mut [u8]) -> usize {
+        dispatch!(self, |p| p.fill(buf))
+    }
+
+    fn fill_period(&mut self, buf: &mut [u8; MAX_PERIOD as usize]) {
+        dispatch!(self, |p| p.fill_period(buf))
+    }
+}
+
+fn parse_num<T: FromStr>(s: &str) -> Result<T, String> {
+    let parsed = match s.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16)
+            .ok()
+            .and_then(|x| x.to_string().parse().ok()),
+        None => s.parse().ok(),
+    };
+    parsed.ok_or_else(|| format!("invalid number: {}", s))
+}
+
+fn parse_list<T: FromStr>(s: &str) -> Result<Vec<T>, String> {
+    s.split(',')
+        .map(|item| {
+            item.trim()
+                .parse()
+                .map_err(|_| format!("invalid list item: {}", item))
+        })
+        .collect()
+}
+
+////////////
+// Output //
+////////////
+
+/// Write the first `opts.k` (or all) indices of `opts.num_periods()` consecutive
+/// periods, one period at a time, so even huge outputs don't build up in
+/// memory.
+fn write_output(
+    out: &mut impl Write,
+    opts: &Options,
+    seed: u64,
+    source: &mut Source,
+) -> io::Result<()> {
+    let k = opts.num_idxs_per_period();
+    let mut idxs = Vec::with_capacity(k as usize);
+    let join = |idxs: &[u8], sep: &str| {
+        idxs.iter()
+            .map(|idx| idx.to_string())
+            .collect::<Vec<_>>()
+            .join(sep)
+    };
+
+    match opts.format {
+        Format::Plain => {}
+        Format::Csv => writeln!(out, "period,position,index")?,
+        Format::Json => write!(
+            out,
+            "{{\"algo\":\"{}\",\"seed\":{},\"n\":{},\"k\":{},\"periods\":[",
+            opts.algo.name(),
+            seed,
+            opts.n,
+            k,
+        )?,
+    }
+    for period in 0..opts.num_periods() {
+        source.reset();
+        idxs.clear();
+        idxs.extend((0..k).map(|_| source.next_item()));
+
+        match opts.format {
+            Format::Plain => writeln!(out, "{}", join(&idxs, " "))?,
+            Format::Csv => {
+                for (position, idx) in idxs.iter().enumerate() {
+                    writeln!(out, "{},{},{}", period, position, idx)?;
+                }
+            }
+            Format::Json => {
+                let sep = if period == 0 { "" } else { "," };
+                write!(out, "{}[{}]", sep, join(&idxs, ","))?;
+            }
+        }
+    }
+    if opts.format == Format::Json {
+        writeln!(out, "]}}")?;
+    }
+    Ok(())
+}
+
+fn main() {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let opts = match Options::parse(&args) {
+        Ok(Some(opts)) => opts,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return;
+        }
+        Err(err) => {
+            eprintln!("error: {}\n\n{}", err, USAGE);
+            process::exit(2);
+        }
+    };
+
+    let seed = opts.seed.unwrap_or_else(|| {
+        let seed = rand::random();
+        eprintln!("seed: {}", seed);
+        seed
+    });
+    let mut source = opts.new_source(seed);
+
+    if opts.command == Command::Stats {
+        let Source::Permuter(perm) = &mut source else {
+            unreachable!("parse rejects stats with shuffle-bag");
+        };
+        println!(
+            "{}, n = {}, {} periods, seed {}\n",
+            opts.algo.name(),
+            opts.n,
+            opts.num_periods(),
+            seed
+        );
+        let results = stats::run(perm, opts.num_periods());
+        print!("{}", stats::render(&results));
+        if !results.iter().all(stats::TestResult::passed) {
+            process::exit(1);
+        }
+        return;
+    }
+
+    let stdout = io::stdout();
+    let mut out = io::BufWriter::new(stdout.lock());
+    if let Err(err) = write_output(&mut out, &opts, seed, &mut source).and_then(|()| out.flush()) {
+        // e.g., a closed pipe from `fastperm ... | head`
+        if err.kind() != io::ErrorKind::BrokenPipe {
+            eprintln!("error: {}", err);
+            process::exit(1);
+        }
+    }
+}
+
+//////////
+// Test //
+//////////
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use fastperm::fill_mask;
+
+    fn parse(args: &str) -> Result<Options, String> {
+        let args = args
+            .split_whitespace()
+            .map(String::from)
+            .collect::<Vec<_>>();
+        Options::parse(&args).map(Option::unwrap)
+    }
+
+    fn output(opts: &Options, seed: u64) -> String {
+        let mut source = opts.new_source(seed);
+        let mut out = Vec::new();
+        write_output(&mut out, opts, seed, &mut source).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_parse() {
+        let opts = parse("--algo shuffle-array -n 0x20 -p 3 -k 4 --seed 42 -f json").unwrap();
+        assert_eq!(Algo::ShuffleArray, opts.algo);
+        assert_eq!(32, opts.n);
+        assert_eq!(Command::Generate, opts.command);
+        assert_eq!(3, opts.num_periods());
+        assert_eq!(Some(4), opts.k);
+        assert_eq!(Some(42), opts.seed);
+        assert_eq!(Format::Json, opts.format);
+
+        let opts = parse("-a shuffle-bag --counts 2,1,3").unwrap();
+        assert_eq!(6, opts.n);
+        let opts = parse("-a weighted --weights 1.5,0,2").unwrap();
+        assert_eq!(3, opts.n);
+
+        assert_eq!(Ok(None), Options::parse(&["-h".to_owned()]));
+        assert!(parse("-a bogo-sort").is_err());
+        assert!(parse("-n 65").is_err());
+        assert!(parse("-n 4 -k 5").is_err());
+        assert!(parse("-a derangement -n 1").is_err());
+        assert!(parse("-a lehmer -n 21").is_err());
+        assert!(parse("-a shuffle-bag").is_err());
+        assert!(parse("-a weighted --weights 1e308,1e308").is_err());
+        assert!(parse("-n").is_err());
+
+        let opts = parse("stats -a shuffle -n 16").unwrap();
+        assert_eq!(Command::Stats, opts.command);
+        assert_eq!(stats::DEFAULT_NUM_PERIODS, opts.num_periods());
+        assert!(parse("stats -n 16 -k 3").is_err());
+        assert!(parse("stats -n 1").is_err());
+        assert!(parse("stats -n 16 -p 0").is_err());
+        assert!(parse("stats -n 16 -p 1").is_err());
+        assert!(parse("stats -n 16 -p 2").is_ok());
+        assert!(parse("stats -a shuffle-bag --counts 0,0,0,1,1").is_err());
+    }
+
+    #[test]
+    fn test_every_algo_outputs_permutations() {
+        for (name, _) in Algo::ALL {
+            let args = match name {
+                "shuffle-bag" => format!("-a {} --counts 1,1,1,1,1,1,1,1 -p 5", name),
+                "weighted" => format!("-a {} --weights 1,2,3,4,5,6,7,8 -p 5", name),
+                _ => format!("-a {} -n 8 -p 5", name),
+            };
+            let opts = parse(&args).unwrap();
+            for line in output(&opts, 123).lines() {
+                let idxs = line.split(' ').map(|idx| idx.parse().unwrap());
+                assert_eq!((0xff, 8), fill_mask(idxs), "{}", name);
+            }
+        }
+    }
+
+    #[test]
+    fn test_output_formats() {
+        let opts = parse("-n 8 -p 2 -k 3 --seed 7").unwrap();
+        let plain = output(&opts, 7);
+        assert_eq!(2, plain.lines().count());
+        assert!(plain.lines().all(|line| line.split(' ').count() == 3));
+        // reproducible
+        assert_eq!(plain, output(&opts, 7));
+
+        let csv = output(
+            &Options {
+                format: Format::Csv,
+                ..opts.clone()
+            },
+            7,
+        );
+        assert_eq!(Some("period,position,index"), csv.lines().next());
+        assert_eq!(7, csv.lines().count());
+
+        let json = output(
+            &Options {
+                format: Format::Json,
+                ..opts
+            },
+            7,
+        );
+        let expected_prefix = "{\"algo\":\"bit-scatter\",\"seed\":7,\"n\":8,\"k\":3,\"periods\":[[";
+        assert!(json.starts_with(expected_prefix), "{}", json);
+        assert!(json.ends_with("]]}\n"));
+    }
+}
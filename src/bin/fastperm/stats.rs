@@ -0,0 +1,305 @@
+//! `fastperm stats`: quick empirical checks that a permuter's periods look like
+//! independent, uniformly random permutations.
+//!
+//! Each test is a chi-square test on counts over many periods:
+//!
+//! * `position_frequency`: how often each index lands at each position. For
+//!   uniform permutations every cell of the `n x n` table is equally likely.
+//! * `adjacent_pair`: the ordered pair of indices at adjacent positions `j` and
+//!   `j + 1`, using one pair per period with `j` cycling through the
+//!   positions. Each of the `n * (n - 1)` distinct pairs is equally likely.
+//! * `cross_period`: the indices at the same position in consecutive periods.
+//!   For independent periods, every cell of the `n x n` table is equally
+//!   likely.
+//!
+//! The constrained permuters, e.g., `sattolo`, `derangement`, or `weighted`,
+//! aren't uniform by design, so they're expected to fail some of these.
+
+use fastperm::{SmallIndexPermutations, MAX_PERIOD};
+use std::fmt::Write as _;
+
+pub const DEFAULT_NUM_PERIODS: u64 = 1_000_000;
+
+/// A test fails if its p-value is below `ALPHA` or above `1 - ALPHA`, since a
+/// fit that's too good to be random is just as suspicious as a bad one.
+const ALPHA: f64 = 0.001;
+
+pub struct TestResult {
+    pub name: &'static str,
+    pub statistic: f64,
+    pub df: f64,
+    pub p_value: f64,
+}
+
+impl TestResult {
+    pub fn passed(&self) -> bool {
+        (ALPHA..=1.0 - ALPHA).contains(&self.p_value)
+    }
+
+    fn new(name: &'static str, statistic: f64, df: f64) -> Self {
+        Self {
+            name,
+            statistic,
+            df,
+            p_value: chi_square_sf(statistic, df),
+        }
+    }
+}
+
+/// Run every test on `num_periods` whole periods of `perm`, which needs a
+/// period of at least 2.
+pub fn run<P: SmallIndexPermutations>(perm: &mut P, num_periods: u64) -> Vec<TestResult> {
+    let n = perm.period() as usize;
+    assert!(n >= 2 && num_periods >= 2);
+
+    let mut position = vec![0_u64; n * n];
+    let mut adjacent = vec![0_u64; n * n];
+    let mut cross = vec![0_u64; n * n];
+    let mut idxs = [0_u8; MAX_PERIOD as usize];
+    let mut prev_idxs = [0_u8; MAX_PERIOD as usize];
+    for period in 0..num_periods {
+        perm.fill_period(&mut idxs);
+        let idxs = &idxs[..n];
+
+        for (pos, &idx) in idxs.iter().enumerate() {
+            position[pos * n + idx as usize] += 1;
+        }
+        let j = (period % (n as u64 - 1)) as usize;
+        adjacent[idxs[j] as usize * n + idxs[j + 1] as usize] += 1;
+        if period > 0 {
+            for (&prev, &idx) in prev_idxs.iter().zip(idxs) {
+                cross[prev as usize * n + idx as usize] += 1;
+            }
+        }
+        prev_idxs[..n].copy_from_slice(idxs);
+    }
+
+    vec![
+        permutation_table_test("position_frequency", &position, n, num_periods),
+        adjacent_pair_test(&adjacent, n, num_periods),
+        permutation_table_test("cross_period", &cross, n, num_periods - 1),
+    ]
+}
+
+// `counts` is the sum of `num_perms` permutation matrices, which for uniform
+// permutations have every cell equally likely. The usual chi-square statistic
+// over such a table is `n / (n - 1)` times a chi-square with `(n - 1)^2`
+// degrees of freedom, since each row and column sum is fixed.
+fn permutation_table_test(
+    name: &'static str,
+    counts: &[u64],
+    n: usize,
+    num_perms: u64,
+) -> TestResult {
+    let expected = num_perms as f64 / n as f64;
+    let statistic = counts
+        .iter()
+        .map(|&count| (count as f64 - expected).powi(2) / expected)
+        .sum::<f64>();
+    let n = n as f64;
+    TestResult::new(name, statistic * (n - 1.0) / n, (n - 1.0).powi(2))
+}
+
+// `counts` holds one pair per period, so it's a plain multinomial sample over
+// the `n * (n - 1)` distinct ordered pairs. A pair with a repeated index can't
+// come from a permutation at all.
+fn adjacent_pair_test(counts: &[u64], n: usize, num_pairs: u64) -> TestResult {
+    let num_cells = n * (n - 1);
+    let expected = num_pairs as f64 / num_cells as f64;
+    let mut statistic = 0.0;
+    for a in 0..n {
+        for b in 0..n {
+            let count = counts[a * n + b] as f64;
+            statistic += if a != b {
+                (count - expected).powi(2) / expected
+            } else if count > 0.0 {
+                f64::INFINITY
+            } else {
+                0.0
+            };
+        }
+    }
+    TestResult::new("adjacent_pair", statistic, (num_cells - 1) as f64)
+}
+
+pub fn render(results: &[TestResult]) -> String {
+    let mut out = format!(
+        "{:<20} {:>12} {:>8} {:>10}  verdict\n",
+        "test", "statistic", "df", "p-value"
+    );
+    for result in results {
+        writeln!(
+            out,
+            "{:<20} {:>12.2} {:>8} {:>10.4}  {}",
+            result.name,
+            result.statistic,
+            result.df,
+            result.p_value,
+            if result.passed() { "pass" } else { "FAIL" },
+        )
+        .unwrap();
+    }
+    out
+}
+
+////////////////
+// Chi-square //
+////////////////
+
+/// `P(X > x)` for `X ~ χ²(df)`, i.e., the regularized upper incomplete gamma
+/// function `Q(df / 2, x / 2)`. See Numerical Recipes, §6.2.
+fn chi_square_sf(x: f64, df: f64) -> f64 {
+    if x <= 0.0 {
+        return 1.0;
+    }
+    if x.is_infinite() {
+        return 0.0;
+    }
+    let (a, x) = (df / 2.0, x / 2.0);
+    if x < a + 1.0 {
+        1.0 - gamma_p_series(a, x)
+    } else {
+        gamma_q_continued_fraction(a, x)
+    }
+}
+
+const MAX_ITERS: usize = 100_000;
+const EPSILON: f64 = 1e-15;
+
+// `P(a, x)` via its series expansion, which converges quickly for `x < a + 1`.
+fn gamma_p_series(a: f64, x: f64) -> f64 {
+    let mut term = 1.0 / a;
+    let mut sum = term;
+    let mut ap = a;
+    for _ in 0..MAX_ITERS {
+        ap += 1.0;
+        term *= x / ap;
+        sum += term;
+        if term.abs() < sum.abs() * EPSILON {
+            break;
+        }
+    }
+    sum * (-x + a * x.ln() - ln_gamma(a)).exp()
+}
+
+// `Q(a, x)` via its continued fraction, evaluated with Lentz's method, which
+// converges quickly for `x >= a + 1`.
+fn gamma_q_continued_fraction(a: f64, x: f64) -> f64 {
+    const TINY: f64 = 1e-300;
+    let mut b = x + 1.0 - a;
+    let mut c = 1.0 / TINY;
+    let mut d = 1.0 / b;
+    let mut h = d;
+    for i in 1..MAX_ITERS {
+        let an = -(i as f64) * (i as f64 - a);
+        b += 2.0;
+        d = an * d + b;
+        if d.abs() < TINY {
+            d = TINY;
+        }
+        c = b + an / c;
+        if c.abs() < TINY {
+            c = TINY;
+        }
+        d = 1.0 / d;
+        let delta = d * c;
+        h *= delta;
+        if (delta - 1.0).abs() < EPSILON {
+            break;
+        }
+    }
+    (-x + a * x.ln() - ln_gamma(a)).exp() * h
+}
+
+// Lanczos approximation, with `g = 7`, accurate to about 15 digits.
+fn ln_gamma(x: f64) -> f64 {
+    const COEFFS: [f64; 9] = [
+        0.999_999_999_999_809_9,
+        676.520_368_121_885_1,
+        -1_259.139_216_722_402_8,
+        771.323_428_777_653_1,
+        -176.615_029_162_140_6,
+        12.507_343_278_686_905,
+        -0.138_571_095_265_720_12,
+        9.984_369_578_019_572e-6,
+        1.505_632_735_149_311_6e-7,
+    ];
+    let x = x - 1.0;
+    let t = x + 7.5;
+    let sum = COEFFS[1..]
+        .iter()
+        .enumerate()
+        .fold(COEFFS[0], |sum, (i, &coeff)| {
+            sum + coeff / (x + i as f64 + 1.0)
+        });
+    0.5 * (2.0 * std::f64::consts::PI).ln() + (x + 0.5) * t.ln() - t + sum.ln()
+}
+
+//////////
+// Test //
+//////////
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use fastperm::{stable::StableRng, BitScatter, Sattolo, ShuffleArrayIncremental};
+    use rand::SeedableRng;
+
+    fn assert_close(expected: f64, actual: f64) {
+        assert!(
+            (expected - actual).abs() < 1e-6,
+            "expected: {}, actual: {}",
+            expected,
+            actual
+        );
+    }
+
+    #[test]
+    fn test_chi_square_sf() {
+        // critical values from standard tables
+        assert_close(0.05, chi_square_sf(3.841_459, 1.0));
+        assert_close(0.01, chi_square_sf(23.209_251, 10.0));
+        assert_close(0.5, chi_square_sf(99.334_129, 100.0));
+        // large df, e.g., position_frequency for n = 64: the median is about
+        // `df * (1 - 2 / (9 * df))^3`
+        assert!((0.5 - chi_square_sf(3_968.334, 3_969.0)).abs() < 1e-3);
+        assert_close(1.0, chi_square_sf(0.0, 5.0));
+        assert_close(0.0, chi_square_sf(f64::INFINITY, 5.0));
+        // exact for df = 2: `exp(-x / 2)`
+        assert_close((-3.0_f64).exp(), chi_square_sf(6.0, 2.0));
+        assert_close(24.0_f64.ln(), ln_gamma(5.0));
+    }
+
+    #[test]
+    fn test_uniform_permuters_pass() {
+        for n in [2, 3, 8] {
+            let mut perm = BitScatter::new(StableRng::seed_from_u64(1), n);
+            let results = run(&mut perm, 20_000);
+            assert!(
+                results.iter().all(TestResult::passed),
+                "{}",
+                render(&results)
+            );
+
+            let mut perm = ShuffleArrayIncremental::new(StableRng::seed_from_u64(2), n);
+            let results = run(&mut perm, 20_000);
+            assert!(
+                results.iter().all(TestResult::passed),
+                "{}",
+                render(&results)
+            );
+        }
+    }
+
+    #[test]
+    fn test_sattolo_fails() {
+        // Sattolo only generates n-cycles, so it can't be uniform
+        let mut perm = Sattolo::new(StableRng::seed_from_u64(3), 8);
+        let results = run(&mut perm, 20_000);
+        assert!(
+            !results.iter().all(TestResult::passed),
+            "{}",
+            render(&results)
+        );
+    }
+}
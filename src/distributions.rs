@@ -0,0 +1,126 @@
+use crate::{
+    perm::Perm64, subset::random_subset_mask, ShuffleArrayIncremental, SmallIndexPermutations,
+    INDEXES, MAX_PERIOD,
+};
+use rand::{distributions::Distribution, Rng};
+
+/// A [`Distribution`] over the permutations of `[0, n)`, so permutations can
+/// be drawn with `rng.sample(..)` without constructing a permuter.
+///
+/// Samples as a `([u8; MAX_PERIOD], u8)` pair of the permutation, in the first
+/// `n` entries, and `n`; or as a [`Perm64`]. Backed by one period of
+/// [`ShuffleArrayIncremental`], the fastest permuter for whole periods.
+///
+/// ## Example
+///
+/// ```rust
+/// use fastperm::distributions::UniformPermutation;
+/// use rand::{rngs::SmallRng, Rng, SeedableRng};
+///
+/// let mut rng = SmallRng::seed_from_u64(123);
+/// let (idxs, n): ([u8; 64], u8) = rng.sample(UniformPermutation::new(5));
+/// let mut sorted = idxs[..n as usize].to_vec();
+/// sorted.sort_unstable();
+/// assert_eq!(vec![0, 1, 2, 3, 4], sorted);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct UniformPermutation {
+    n: u8,
+}
+
+impl UniformPermutation {
+    pub fn new(n: u8) -> Self {
+        assert!(n <= MAX_PERIOD && n > 0);
+        Self { n }
+    }
+}
+
+impl Distribution<([u8; MAX_PERIOD as usize], u8)> for UniformPermutation {
+    #[inline]
+    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> ([u8; MAX_PERIOD as usize], u8) {
+        let mut idxs = INDEXES;
+        ShuffleArrayIncremental::new(rng, self.n).fill_period(&mut idxs);
+        (idxs, self.n)
+    }
+}
+
+impl Distribution<Perm64> for UniformPermutation {
+    #[inline]
+    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> Perm64 {
+        Perm64::from_period(&mut ShuffleArrayIncremental::new(rng, self.n))
+    }
+}
+
+/// A [`Distribution`] over the `k`-subsets of `[0, n)`, sampled as `u64` bit
+/// masks via [`random_subset_mask`].
+///
+/// ## Example
+///
+/// ```rust
+/// use fastperm::distributions::UniformSubset;
+/// use rand::{rngs::SmallRng, Rng, SeedableRng};
+///
+/// let rng = SmallRng::seed_from_u64(123);
+/// for mask in rng.sample_iter(UniformSubset::new(10, 3)).take(100) {
+///     assert_eq!(3, mask.count_ones());
+///     assert!(mask < 1 << 10);
+/// }
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct UniformSubset {
+    n: u8,
+    k: u8,
+}
+
+impl UniformSubset {
+    pub fn new(n: u8, k: u8) -> Self {
+        assert!(n <= MAX_PERIOD && k <= n);
+        Self { n, k }
+    }
+}
+
+impl Distribution<u64> for UniformSubset {
+    #[inline]
+    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> u64 {
+        random_subset_mask(rng, self.n, self.k)
+    }
+}
+
+//////////
+// Test //
+//////////
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{fill_mask, index_mask, test::arb_small_rng};
+    use proptest::prelude::*;
+    use rand::Rng;
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(2000))]
+
+        #[test]
+        fn uniform_permutation_is_permutation(n in 1..=MAX_PERIOD, mut rng in arb_small_rng()) {
+            let dist = UniformPermutation::new(n);
+
+            let (idxs, period): ([u8; MAX_PERIOD as usize], u8) = rng.sample(dist);
+            assert_eq!(n, period);
+            let (mask, count) = fill_mask(idxs[..n as usize].iter().copied());
+            assert_eq!(n, count);
+            assert_eq!(index_mask(n as u32), mask);
+
+            let perm: Perm64 = rng.sample(dist);
+            assert_eq!(Some(perm), Perm64::from_slice(perm.as_slice()));
+        }
+
+        #[test]
+        fn uniform_subset_has_k_bits(n in 1..=MAX_PERIOD, k in 0..=MAX_PERIOD, rng in arb_small_rng()) {
+            let k = k.min(n);
+            for mask in rng.sample_iter(UniformSubset::new(n, k)).take(4) {
+                assert_eq!(k as u32, mask.count_ones());
+                assert_eq!(0, mask & !index_mask(n as u32));
+            }
+        }
+    }
+}
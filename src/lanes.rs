@@ -0,0 +1,243 @@
+use crate::{fastmap32, index_mask, select64::select64, MAX_PERIOD};
+use rand::Rng;
+
+/// Number of independent permutations advanced per step by [`BitScatterX8`].
+pub const LANES: usize = 8;
+
+/// Eight independent [`BitScatter`](crate::BitScatter) states advanced in
+/// lockstep. Each call to [`next_indices`](BitScatterX8::next_indices) yields
+/// the next index of every lane's permutation.
+///
+/// On x86_64 with AVX2, the lanes are processed four at a time using a
+/// vectorized version of [`select64_fallback`](crate::select64::select64_fallback);
+/// otherwise each lane falls back to the scalar [`select64`].
+///
+/// All lanes draw from the one `rng`, so the lanes' permutations are as
+/// independent as the rng's output.
+///
+/// ## Example
+///
+/// ```rust
+/// use fastperm::lanes::{BitScatterX8, LANES};
+/// use rand::{rngs::SmallRng, SeedableRng};
+///
+/// let mut lanes = BitScatterX8::new(SmallRng::seed_from_u64(123), 10);
+/// let mut seen = [0_u64; LANES];
+/// for _ in 0..10 {
+///     for (lane, idx) in lanes.next_indices().into_iter().enumerate() {
+///         seen[lane] |= 1 << idx;
+///     }
+/// }
+/// assert!(seen.iter().all(|&mask| mask == 0b11_1111_1111));
+/// ```
+pub struct BitScatterX8<R> {
+    rng: R,
+    unchosen_masks: [u64; LANES],
+    n: u8,
+    // number of remaining indices in every lane
+    m: u8,
+}
+
+impl<R: Rng> BitScatterX8<R> {
+    pub fn new(rng: R, n: u8) -> Self {
+        assert!(n <= MAX_PERIOD && n > 0);
+        Self {
+            rng,
+            unchosen_masks: [index_mask(n as u32); LANES],
+            n,
+            m: n,
+        }
+    }
+
+    #[inline(always)]
+    pub fn period(&self) -> u8 {
+        self.n
+    }
+
+    #[inline(always)]
+    pub fn reset(&mut self) {
+        self.m = self.n;
+        self.unchosen_masks = [index_mask(self.n as u32); LANES];
+    }
+
+    /// Return the next index of each lane's permutation.
+    #[inline]
+    pub fn next_indices(&mut self) -> [u8; LANES] {
+        if self.m == 0 {
+            self.reset();
+        }
+
+        let mut samples = [0_u32; LANES];
+        for sample in &mut samples {
+            *sample = self.rng.next_u32();
+        }
+
+        #[cfg(target_arch = "x86_64")]
+        let idxs = if is_x86_feature_detected!("avx2") {
+            unsafe { next_indices_avx2(&samples, self.m, &mut self.unchosen_masks) }
+        } else {
+            next_indices_scalar(&samples, self.m, &mut self.unchosen_masks)
+        };
+        #[cfg(not(target_arch = "x86_64"))]
+        let idxs = next_indices_scalar(&samples, self.m, &mut self.unchosen_masks);
+
+        self.m -= 1;
+        idxs
+    }
+}
+
+fn next_indices_scalar(samples: &[u32; LANES], m: u8, masks: &mut [u64; LANES]) -> [u8; LANES] {
+    let mut idxs = [0_u8; LANES];
+    for lane in 0..LANES {
+        let idx_mspace = fastmap32(samples[lane], m as u32) as u8;
+        let idx_nspace = select64(idx_mspace, masks[lane]);
+        masks[lane] &= !(1_u64 << (idx_nspace as u32));
+        idxs[lane] = idx_nspace;
+    }
+    idxs
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+unsafe fn next_indices_avx2(
+    samples: &[u32; LANES],
+    m: u8,
+    masks: &mut [u64; LANES],
+) -> [u8; LANES] {
+    use std::arch::x86_64::*;
+
+    let m = _mm256_set1_epi64x(m as i64);
+    let one = _mm256_set1_epi64x(1);
+    let mut out = [0_u64; LANES];
+
+    for half in 0..(LANES / 4) {
+        let lo = half * 4;
+
+        // fastmap32 on four lanes: (sample * m) >> 32
+        let samples = _mm_loadu_si128(samples[lo..].as_ptr() as *const __m128i);
+        let samples = _mm256_cvtepu32_epi64(samples);
+        let idx_mspace = _mm256_srli_epi64::<32>(_mm256_mul_epu32(samples, m));
+
+        let mask = _mm256_loadu_si256(masks[lo..].as_ptr() as *const __m256i);
+        let idx_nspace = select64x4_avx2(idx_mspace, mask);
+
+        // unset the newly sampled index in each lane
+        let mask = _mm256_andnot_si256(_mm256_sllv_epi64(one, idx_nspace), mask);
+        _mm256_storeu_si256(masks[lo..].as_mut_ptr() as *mut __m256i, mask);
+        _mm256_storeu_si256(out[lo..].as_mut_ptr() as *mut __m256i, idx_nspace);
+    }
+
+    let mut idxs = [0_u8; LANES];
+    for lane in 0..LANES {
+        idxs[lane] = out[lane] as u8;
+    }
+    idxs
+}
+
+// `select64_fallback` on four 64-bit lanes at once.
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+unsafe fn select64x4_avx2(
+    idx: std::arch::x86_64::__m256i,
+    mask: std::arch::x86_64::__m256i,
+) -> std::arch::x86_64::__m256i {
+    use std::arch::x86_64::*;
+
+    let m1 = _mm256_set1_epi64x(0x5555_5555_5555_5555);
+    let m2 = _mm256_set1_epi64x(0x3333_3333_3333_3333);
+    let m4 = _mm256_set1_epi64x(0x0F0F_0F0F_0F0F_0F0F);
+    let m8 = _mm256_set1_epi64x(0x00FF_00FF_00FF_00FF);
+    let m16 = _mm256_set1_epi64x(0x0000_FFFF_0000_FFFF);
+
+    let b0 = mask;
+    let b1 = _mm256_add_epi64(
+        _mm256_and_si256(b0, m1),
+        _mm256_and_si256(_mm256_srli_epi64::<1>(b0), m1),
+    );
+    let b2 = _mm256_add_epi64(
+        _mm256_and_si256(b1, m2),
+        _mm256_and_si256(_mm256_srli_epi64::<2>(b1), m2),
+    );
+    let b3 = _mm256_and_si256(_mm256_add_epi64(b2, _mm256_srli_epi64::<4>(b2)), m4);
+    let b4 = _mm256_and_si256(_mm256_add_epi64(b3, _mm256_srli_epi64::<8>(b3)), m8);
+    let b5 = _mm256_and_si256(_mm256_add_epi64(b4, _mm256_srli_epi64::<16>(b4)), m16);
+
+    let mut idx = idx;
+    let mut r = _mm256_setzero_si256();
+
+    // one step of the binary search: in the lanes where `idx >= b`, subtract
+    // `b` from `idx` and advance `r` by `width`.
+    macro_rules! step {
+        ($b:expr, $field:expr, $width:expr) => {{
+            let b = _mm256_and_si256(_mm256_srlv_epi64($b, r), _mm256_set1_epi64x($field));
+            // lanes where `b > idx`, i.e., where we don't take the step
+            let lt = _mm256_cmpgt_epi64(b, idx);
+            idx = _mm256_sub_epi64(idx, _mm256_andnot_si256(lt, b));
+            r = _mm256_add_epi64(r, _mm256_andnot_si256(lt, _mm256_set1_epi64x($width)));
+        }};
+    }
+
+    step!(b5, 0xFFFF_FFFF, 32);
+    step!(b4, 0xFFFF, 16);
+    step!(b3, 0xFF, 8);
+    step!(b2, 0xF, 4);
+    step!(b1, 0x3, 2);
+
+    // last step doesn't need to update `idx`
+    let b = _mm256_and_si256(_mm256_srlv_epi64(b0, r), _mm256_set1_epi64x(0x1));
+    let lt = _mm256_cmpgt_epi64(b, idx);
+    _mm256_add_epi64(r, _mm256_andnot_si256(lt, _mm256_set1_epi64x(1)))
+}
+
+//////////
+// Test //
+//////////
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::arb_small_rng;
+    use proptest::prelude::*;
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(2000))]
+
+        #[test]
+        fn bit_scatter_x8_lanes_are_permutations(n in 1..=MAX_PERIOD, rng in arb_small_rng()) {
+            let mut lanes = BitScatterX8::new(rng, n);
+            // run a couple of periods to cover the implicit reset
+            for _ in 0..2 {
+                let mut masks = [0_u64; LANES];
+                for _ in 0..n {
+                    for (mask, idx) in masks.iter_mut().zip(lanes.next_indices()) {
+                        assert_eq!(0, *mask & (1 << idx));
+                        *mask |= 1 << idx;
+                    }
+                }
+                assert!(masks.iter().all(|&mask| mask == index_mask(n as u32)));
+            }
+        }
+
+        #[cfg(target_arch = "x86_64")]
+        #[test]
+        fn next_indices_avx2_eq_scalar(
+            n in 1..=MAX_PERIOD,
+            samples in any::<[u32; LANES]>(),
+            masks in any::<[u64; LANES]>(),
+        ) {
+            if !is_x86_feature_detected!("avx2") {
+                return Ok(());
+            }
+            // keep every lane's mask non-empty and `m` valid for all of them
+            let masks = masks.map(|mask| (mask & index_mask(n as u32)) | 1);
+            let m = masks.iter().map(|mask| mask.count_ones()).min().unwrap();
+
+            let mut masks_scalar = masks;
+            let mut masks_avx2 = masks;
+            let idxs_scalar = next_indices_scalar(&samples, m as u8, &mut masks_scalar);
+            let idxs_avx2 = unsafe { next_indices_avx2(&samples, m as u8, &mut masks_avx2) };
+            assert_eq!(idxs_scalar, idxs_avx2);
+            assert_eq!(masks_scalar, masks_avx2);
+        }
+    }
+}
@@ -0,0 +1,242 @@
+use crate::{
+    index_mask, perm::Perm64, select64::select64, SmallIndexPermutations, INDEXES, MAX_PERIOD,
+};
+use rand::Rng;
+
+/// The largest `n` with `n! < 2^64`, i.e., the largest period whose
+/// permutations can all be ranked in a `u64`.
+pub const MAX_LEHMER_PERIOD: u8 = 20;
+
+const fn make_factorials() -> [u64; MAX_LEHMER_PERIOD as usize + 1] {
+    let mut i = 1;
+    let mut factorials = [1_u64; MAX_LEHMER_PERIOD as usize + 1];
+    while i <= MAX_LEHMER_PERIOD as usize {
+        factorials[i] = factorials[i - 1] * i as u64;
+        i += 1;
+    }
+    factorials
+}
+
+/// `FACTORIALS[n] == n!` for `n <= MAX_LEHMER_PERIOD`.
+pub const FACTORIALS: [u64; MAX_LEHMER_PERIOD as usize + 1] = make_factorials();
+
+/// The lexicographic rank in `[0, n!)` of the permutation `perm` of `[0, n)`,
+/// computed from its Lehmer code.
+///
+/// Panics if `perm` is empty, longer than `MAX_LEHMER_PERIOD`, or not a
+/// permutation of `[0, n)`.
+///
+/// ## Example
+///
+/// ```rust
+/// use fastperm::lehmer::{rank, unrank};
+///
+/// assert_eq!(0, rank(&[0, 1, 2]));
+/// assert_eq!(3, rank(&[1, 2, 0]));
+/// assert_eq!(5, rank(&[2, 1, 0]));
+/// assert_eq!(&[1, 2, 0], unrank(3, 3).as_slice());
+/// ```
+pub fn rank(perm: &[u8]) -> u64 {
+    let n = perm.len();
+    assert!(n <= MAX_LEHMER_PERIOD as usize && n > 0);
+    let mut unused = index_mask(n as u32);
+    let mut r = 0;
+    for (i, &idx) in perm.iter().enumerate() {
+        // also catches `idx >= n`, since `unused` only has bits below `n`
+        assert!(
+            idx < MAX_PERIOD && unused & (1 << idx) != 0,
+            "not a permutation: {:?}",
+            perm
+        );
+        // the Lehmer digit: how many unused indices are smaller than `idx`
+        let digit = (unused & ((1_u64 << idx) - 1)).count_ones() as u64;
+        r += digit * FACTORIALS[n - 1 - i];
+        unused &= !(1_u64 << idx);
+    }
+    r
+}
+
+/// The permutation of `[0, n)` with lexicographic rank `r`. Inverse of
+/// [`rank`].
+///
+/// Panics if `n == 0`, `n > MAX_LEHMER_PERIOD`, or `r >= n!`.
+pub fn unrank(n: u8, r: u64) -> Perm64 {
+    assert!(n <= MAX_LEHMER_PERIOD && n > 0);
+    assert!(r < FACTORIALS[n as usize]);
+    let mut idxs = INDEXES;
+    unrank_into(n, r, &mut idxs);
+    Perm64::from_slice(&idxs[..n as usize]).unwrap()
+}
+
+#[inline]
+fn unrank_into(n: u8, mut r: u64, out: &mut [u8; MAX_PERIOD as usize]) {
+    let mut unused = index_mask(n as u32);
+    for i in 0..n as usize {
+        let f = FACTORIALS[n as usize - 1 - i];
+        let digit = (r / f) as u8;
+        r %= f;
+        // the `digit`'th smallest unused index
+        let idx = select64(digit, unused);
+        unused &= !(1_u64 << idx);
+        out[i] = idx;
+    }
+}
+
+////////////////////
+// LehmerPermuter //
+////////////////////
+
+/// A permuter that draws a single uniform `u64` in `[0, n!)` per period and
+/// unranks it into the whole period at once.
+///
+/// Only supports periods up to [`MAX_LEHMER_PERIOD`].
+pub struct LehmerPermuter<R> {
+    rng: R,
+    idxs: [u8; MAX_PERIOD as usize],
+    n: u8,
+    idx: u8,
+}
+
+impl<R: Rng> LehmerPermuter<R> {
+    pub fn new(rng: R, n: u8) -> Self {
+        assert!(n <= MAX_LEHMER_PERIOD && n > 0);
+        Self {
+            rng,
+            idxs: INDEXES,
+            n,
+            idx: n,
+        }
+    }
+
+    #[inline]
+    fn draw(&mut self) -> u64 {
+        // unlike the other permuters, no modulo bias here: n! can be close to
+        // 2^64, so `gen_range` does the rejection sampling for us.
+        self.rng.gen_range(0..FACTORIALS[self.n as usize])
+    }
+}
+
+impl<R: Rng> SmallIndexPermutations for LehmerPermuter<R> {
+    #[inline(always)]
+    fn period(&self) -> u8 {
+        self.n
+    }
+
+    // draw the next period lazily, so a `reset` right before the period ends
+    // doesn't waste a draw.
+    #[inline(always)]
+    fn reset(&mut self) {
+        self.idx = self.n;
+    }
+
+    #[inline]
+    fn next_index(&mut self) -> u8 {
+        if self.idx == self.n {
+            let r = self.draw();
+            unrank_into(self.n, r, &mut self.idxs);
+            self.idx = 0;
+        }
+        debug_assert!(self.idx < self.n);
+        let r = unsafe { *self.idxs.get_unchecked(self.idx as usize) };
+        self.idx += 1;
+        r
+    }
+
+    fn fill_period(&mut self, buf: &mut [u8; MAX_PERIOD as usize]) {
+        let r = self.draw();
+        unrank_into(self.n, r, buf);
+        self.idx = self.n;
+    }
+}
+
+//////////
+// Test //
+//////////
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{fill_mask, test::arb_small_rng};
+    use proptest::prelude::*;
+
+    #[test]
+    fn test_factorials() {
+        assert_eq!(1, FACTORIALS[0]);
+        assert_eq!(120, FACTORIALS[5]);
+        assert_eq!(2_432_902_008_176_640_000, FACTORIALS[20]);
+    }
+
+    #[test]
+    fn test_rank_unrank_exhaustive() {
+        for n in 1..=6_u8 {
+            let mut prev: Option<Perm64> = None;
+            for r in 0..FACTORIALS[n as usize] {
+                let perm = unrank(n, r);
+                assert_eq!(r, rank(perm.as_slice()));
+                // unrank should walk the permutations in lexicographic order
+                if let Some(prev) = prev {
+                    assert!(prev.as_slice() < perm.as_slice());
+                }
+                prev = Some(perm);
+            }
+        }
+    }
+
+    #[test]
+    fn test_rank_unrank_extremes() {
+        let n = MAX_LEHMER_PERIOD;
+        assert!(unrank(n, 0).is_identity());
+        let last = unrank(n, FACTORIALS[n as usize] - 1);
+        let reversed = (0..n).rev().collect::<Vec<_>>();
+        assert_eq!(&reversed[..], last.as_slice());
+    }
+
+    #[test]
+    #[should_panic(expected = "not a permutation")]
+    fn test_rank_repeated_idx() {
+        rank(&[0, 1, 1]);
+    }
+
+    #[test]
+    #[should_panic(expected = "not a permutation")]
+    fn test_rank_out_of_range_idx() {
+        rank(&[0, 3, 1]);
+    }
+
+    #[test]
+    #[should_panic(expected = "not a permutation")]
+    fn test_rank_huge_idx() {
+        rank(&[0, 200]);
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(10000))]
+
+        #[test]
+        fn rank_unrank_roundtrip(n in 1..=MAX_LEHMER_PERIOD, r in any::<u64>()) {
+            let r = r % FACTORIALS[n as usize];
+            assert_eq!(r, rank(unrank(n, r).as_slice()));
+        }
+
+        #[test]
+        fn lehmer_permuter_is_permutation(n in 1..=MAX_LEHMER_PERIOD, rng in arb_small_rng()) {
+            let mut lehmer = LehmerPermuter::new(rng, n);
+            for _ in 0..2 {
+                let (mask, count) = fill_mask(lehmer.iter_period());
+                assert_eq!(n, count);
+                assert_eq!(index_mask(n as u32), mask);
+            }
+        }
+
+        #[test]
+        fn lehmer_fill_period_eq_next_index(n in 1..=MAX_LEHMER_PERIOD, rng in arb_small_rng()) {
+            let mut fill = LehmerPermuter::new(rng.clone(), n);
+            let mut next = LehmerPermuter::new(rng, n);
+            let mut buf = [0_u8; MAX_PERIOD as usize];
+            fill.fill_period(&mut buf);
+            let expected = next.iter_period().collect::<Vec<_>>();
+            assert_eq!(&expected[..], &buf[..n as usize]);
+            assert_eq!(next.next_index(), fill.next_index());
+        }
+    }
+}
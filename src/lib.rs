@@ -1,8 +1,18 @@
 use crate::select64::select64;
 use rand::{seq::SliceRandom, Rng};
-use std::iter::{IntoIterator, Iterator, Take};
+use std::iter::{Iterator, Take};
 
+pub mod atomic;
+pub mod distributions;
+pub mod lanes;
+pub mod lehmer;
+pub mod perm;
 pub mod select64;
+pub mod slice;
+pub mod stable;
+pub mod streams;
+pub mod subset;
+pub mod weighted;
 
 pub const MAX_PERIOD: u8 = 64;
 
@@ -17,6 +27,26 @@ pub trait SmallIndexPermutations {
 
     fn reset(&mut self);
 
+    /// Write the next `buf.len()` indices into `buf`, continuing across period
+    /// boundaries exactly as repeated calls to `next_index` would. Returns the
+    /// number of indices written, i.e., `buf.len()`.
+    fn fill(&mut self, buf: &mut [u8]) -> usize {
+        for out in buf.iter_mut() {
+            *out = self.next_index();
+        }
+        buf.len()
+    }
+
+    /// Start a new period and write the whole period into `buf[..period()]`.
+    /// The entries after the period are left untouched.
+    fn fill_period(&mut self, buf: &mut [u8; MAX_PERIOD as usize]) {
+        self.reset();
+        let n = self.period();
+        for out in &mut buf[..n as usize] {
+            *out = self.next_index();
+        }
+    }
+
     #[inline]
     fn iter(&mut self) -> Iter<&mut Self> {
         Iter::new(self)
@@ -56,6 +86,14 @@ impl<T: SmallIndexPermutations + ?Sized> SmallIndexPermutations for &mut T {
     fn reset(&mut self) {
         (**self).reset()
     }
+
+    fn fill(&mut self, buf: &mut [u8]) -> usize {
+        (**self).fill(buf)
+    }
+
+    fn fill_period(&mut self, buf: &mut [u8; MAX_PERIOD as usize]) {
+        (**self).fill_period(buf)
+    }
 }
 
 pub struct Iter<T: SmallIndexPermutations> {
@@ -91,7 +129,7 @@ pub struct Shuffle<R> {
 impl<R: Rng> Shuffle<R> {
     pub fn new(rng: R, n: u8) -> Self {
         assert!(n <= MAX_PERIOD && n > 0);
-        let idxs = (0..n).into_iter().collect::<Vec<_>>();
+        let idxs = (0..n).collect::<Vec<_>>();
         Self { rng, idxs, idx: n }
     }
 }
@@ -114,7 +152,31 @@ impl<R: Rng> SmallIndexPermutations for Shuffle<R> {
     #[inline]
     fn reset(&mut self) {
         self.idx = 0;
-        (&mut self.idxs).shuffle(&mut self.rng);
+        self.idxs.shuffle(&mut self.rng);
+    }
+
+    fn fill(&mut self, buf: &mut [u8]) -> usize {
+        let n = self.period();
+        let mut written = 0;
+        while written < buf.len() {
+            if self.idx == n {
+                self.reset();
+            }
+            // copy out the rest of the current period, or as much as fits
+            let k = ((n - self.idx) as usize).min(buf.len() - written);
+            let start = self.idx as usize;
+            buf[written..written + k].copy_from_slice(&self.idxs[start..start + k]);
+            self.idx += k as u8;
+            written += k;
+        }
+        written
+    }
+
+    fn fill_period(&mut self, buf: &mut [u8; MAX_PERIOD as usize]) {
+        self.reset();
+        let n = self.period() as usize;
+        buf[..n].copy_from_slice(&self.idxs[..n]);
+        self.idx = n as u8;
     }
 }
 
@@ -166,7 +228,7 @@ impl<R: Rng> SmallIndexPermutations for ShuffleArray<R> {
     #[inline]
     fn reset(&mut self) {
         self.idx = 0;
-        (&mut self.idxs[..self.n as usize]).shuffle(&mut self.rng);
+        self.idxs[..self.n as usize].shuffle(&mut self.rng);
     }
 
     fn next_index(&mut self) -> u8 {
@@ -177,6 +239,30 @@ impl<R: Rng> SmallIndexPermutations for ShuffleArray<R> {
         self.idx += 1;
         r
     }
+
+    fn fill(&mut self, buf: &mut [u8]) -> usize {
+        let n = self.period();
+        let mut written = 0;
+        while written < buf.len() {
+            if self.idx == n {
+                self.reset();
+            }
+            // copy out the rest of the current period, or as much as fits
+            let k = ((n - self.idx) as usize).min(buf.len() - written);
+            let start = self.idx as usize;
+            buf[written..written + k].copy_from_slice(&self.idxs[start..start + k]);
+            self.idx += k as u8;
+            written += k;
+        }
+        written
+    }
+
+    fn fill_period(&mut self, buf: &mut [u8; MAX_PERIOD as usize]) {
+        self.reset();
+        let n = self.period() as usize;
+        buf[..n].copy_from_slice(&self.idxs[..n]);
+        self.idx = n as u8;
+    }
 }
 
 /////////////////////////////
@@ -229,6 +315,261 @@ impl<R: Rng> SmallIndexPermutations for ShuffleArrayIncremental<R> {
         };
         r
     }
+
+    fn fill(&mut self, buf: &mut [u8]) -> usize {
+        let n = self.n;
+        let mut idx = self.idx;
+        for out in buf.iter_mut() {
+            let idx_swap = fastmap32(self.rng.next_u32(), (n - idx) as u32) + idx as u32;
+            self.idxs.swap(idx as usize, idx_swap as usize);
+            debug_assert!(idx < n);
+            *out = unsafe { *self.idxs.get_unchecked(idx as usize) };
+            idx = if idx + 1 == n { 0 } else { idx + 1 };
+        }
+        self.idx = idx;
+        buf.len()
+    }
+
+    fn fill_period(&mut self, buf: &mut [u8; MAX_PERIOD as usize]) {
+        let n = self.n;
+        for idx in 0..n {
+            let idx_swap = fastmap32(self.rng.next_u32(), (n - idx) as u32) + idx as u32;
+            self.idxs.swap(idx as usize, idx_swap as usize);
+        }
+        // the swaps leave the whole period sitting in `idxs[..n]`
+        buf[..n as usize].copy_from_slice(&self.idxs[..n as usize]);
+        self.idx = 0;
+    }
+}
+
+/////////////
+// Sattolo //
+/////////////
+
+/// Like [`ShuffleArrayIncremental`], but each period is a uniformly random
+/// single `n`-cycle (via Sattolo's algorithm): following `i -> perm[i]` from
+/// any index visits every index before returning, e.g., for ring topologies.
+///
+/// Sattolo's algorithm only produces a single cycle when started from the
+/// identity, so each period starts by restoring the identity.
+pub struct Sattolo<R> {
+    rng: R,
+    idxs: [u8; MAX_PERIOD as usize],
+    n: u8,
+    idx: u8,
+}
+
+impl<R: Rng> Sattolo<R> {
+    pub fn new(rng: R, n: u8) -> Self {
+        assert!(n <= MAX_PERIOD && n > 0);
+        Self {
+            rng,
+            idxs: INDEXES,
+            n,
+            idx: 0,
+        }
+    }
+}
+
+impl<R: Rng> SmallIndexPermutations for Sattolo<R> {
+    #[inline(always)]
+    fn period(&self) -> u8 {
+        self.n
+    }
+
+    #[inline(always)]
+    fn reset(&mut self) {
+        self.idx = 0;
+    }
+
+    #[inline]
+    fn next_index(&mut self) -> u8 {
+        if self.idx == 0 {
+            self.idxs[..self.n as usize].copy_from_slice(&INDEXES[..self.n as usize]);
+        }
+
+        // same as `ShuffleArrayIncremental`, except never swap an index with
+        // itself, i.e., sample from `(idx, n)` instead of `[idx, n)`.
+        if self.idx + 1 < self.n {
+            let idx_swap = fastmap32(self.rng.next_u32(), (self.n - self.idx - 1) as u32)
+                + self.idx as u32
+                + 1;
+            self.idxs.swap(self.idx as usize, idx_swap as usize);
+        }
+
+        debug_assert!(self.idx < self.n);
+        let r = unsafe { *self.idxs.get_unchecked(self.idx as usize) };
+        self.idx = if self.idx + 1 == self.n {
+            0
+        } else {
+            self.idx + 1
+        };
+        r
+    }
+}
+
+/////////////////
+// Derangement //
+/////////////////
+
+/// A permuter where each period has no index in the same position as in the
+/// previous period, i.e., `period_k[i] != period_{k-1}[i]` for all `i`. The
+/// first period is compared against the identity, so it's a derangement in
+/// the usual sense. A `reset` mid-period compares the next period against the
+/// whole of the abandoned one.
+///
+/// Each period is uniformly random among all such permutations. It's sampled
+/// up front by running the `ShuffleArrayIncremental` swaps and starting over
+/// as soon as a position repeats, which takes about `e` attempts on average.
+///
+/// Needs `n >= 2`, since there's no way to move a single index.
+pub struct Derangement<R> {
+    rng: R,
+    idxs: [u8; MAX_PERIOD as usize],
+    n: u8,
+    idx: u8,
+}
+
+impl<R: Rng> Derangement<R> {
+    pub fn new(rng: R, n: u8) -> Self {
+        assert!(n <= MAX_PERIOD && n > 1);
+        Self {
+            rng,
+            idxs: INDEXES,
+            n,
+            idx: n,
+        }
+    }
+
+    fn next_period(&mut self) {
+        let n = self.n;
+        let prev = self.idxs;
+        'attempt: loop {
+            // a Fisher-Yates shuffle from any starting order is still uniform,
+            // so each attempt just picks up from where the last one gave up.
+            for idx in 0..n {
+                let idx_swap = fastmap32(self.rng.next_u32(), (n - idx) as u32) + idx as u32;
+                self.idxs.swap(idx as usize, idx_swap as usize);
+                if self.idxs[idx as usize] == prev[idx as usize] {
+                    continue 'attempt;
+                }
+            }
+            break;
+        }
+    }
+}
+
+impl<R: Rng> SmallIndexPermutations for Derangement<R> {
+    #[inline(always)]
+    fn period(&self) -> u8 {
+        self.n
+    }
+
+    #[inline]
+    fn reset(&mut self) {
+        self.idx = 0;
+        self.next_period();
+    }
+
+    fn next_index(&mut self) -> u8 {
+        if self.idx == self.n {
+            self.reset();
+        }
+        let r = unsafe { *self.idxs.get_unchecked(self.idx as usize) };
+        self.idx += 1;
+        r
+    }
+}
+
+////////////////
+// ShuffleBag //
+////////////////
+
+/// A "shuffle bag": each period outputs item `i` exactly `counts[i]` times, in
+/// a uniformly random order, e.g., for "7-bag" style randomness. The period is
+/// the sum of the counts.
+///
+/// Works like [`ShuffleArrayIncremental`], but over an array holding each item
+/// `counts[i]` times instead of over `[0, n)`. It isn't a
+/// [`SmallIndexPermutations`], since its periods repeat items and items can be
+/// `>= period()`, e.g., counts `[0, 0, 1, 1]` give a period of 2 over the items
+/// `2` and `3`.
+///
+/// ## Example
+///
+/// ```rust
+/// use fastperm::ShuffleBag;
+/// use rand::{rngs::SmallRng, SeedableRng};
+///
+/// let mut bag = ShuffleBag::new(SmallRng::seed_from_u64(123), &[2, 0, 1]);
+/// let mut period = bag.iter_period().collect::<Vec<_>>();
+/// period.sort_unstable();
+/// assert_eq!(vec![0, 0, 2], period);
+/// ```
+pub struct ShuffleBag<R> {
+    rng: R,
+    items: [u8; MAX_PERIOD as usize],
+    n: u8,
+    idx: u8,
+}
+
+impl<R: Rng> ShuffleBag<R> {
+    /// Panics if `counts` is longer than `MAX_PERIOD` or if the total count
+    /// isn't in `1..=MAX_PERIOD`.
+    pub fn new(rng: R, counts: &[u8]) -> Self {
+        assert!(counts.len() <= MAX_PERIOD as usize);
+        let total = counts.iter().map(|&count| count as usize).sum::<usize>();
+        assert!(total <= MAX_PERIOD as usize && total > 0);
+
+        let mut items = [0_u8; MAX_PERIOD as usize];
+        let mut n = 0;
+        for (item, &count) in counts.iter().enumerate() {
+            items[n..n + count as usize].fill(item as u8);
+            n += count as usize;
+        }
+        Self {
+            rng,
+            items,
+            n: n as u8,
+            idx: 0,
+        }
+    }
+
+    /// The number of items per period, i.e., the total count.
+    #[inline(always)]
+    pub fn period(&self) -> u8 {
+        self.n
+    }
+
+    /// Start a new period on the next call to [`ShuffleBag::next_item`].
+    #[inline(always)]
+    pub fn reset(&mut self) {
+        self.idx = 0;
+    }
+
+    /// Return the next item, starting a new period once the current one is
+    /// exhausted.
+    #[inline]
+    pub fn next_item(&mut self) -> u8 {
+        let idx_swap = fastmap32(self.rng.next_u32(), (self.n - self.idx) as u32) + self.idx as u32;
+        self.items.swap(self.idx as usize, idx_swap as usize);
+
+        debug_assert!(self.idx < self.n);
+        let r = unsafe { *self.items.get_unchecked(self.idx as usize) };
+        self.idx = if self.idx + 1 == self.n {
+            0
+        } else {
+            self.idx + 1
+        };
+        r
+    }
+
+    /// Start a new period and iterate over all of it.
+    pub fn iter_period(&mut self) -> impl Iterator<Item = u8> + '_ {
+        self.reset();
+        let n = self.n;
+        (0..n).map(move |_| self.next_item())
+    }
 }
 
 ////////////////
@@ -285,6 +626,177 @@ impl<R: Rng> SmallIndexPermutations for BitScatter<R> {
 
         idx_nspace
     }
+
+    fn fill(&mut self, buf: &mut [u8]) -> usize {
+        // keep the state in locals so the loop doesn't round-trip through `self`
+        let mut mask = self.unchosen_mask;
+        let mut m = self.m;
+        for out in buf.iter_mut() {
+            if m == 0 {
+                mask = index_mask(self.n as u32);
+                m = self.n;
+            }
+            let idx_mspace = fastmap32(self.rng.next_u32(), m as u32) as u8;
+            let idx_nspace = select64(idx_mspace, mask);
+            mask &= !(1_u64 << (idx_nspace as u32));
+            m -= 1;
+            *out = idx_nspace;
+        }
+        self.unchosen_mask = mask;
+        self.m = m;
+        buf.len()
+    }
+
+    fn fill_period(&mut self, buf: &mut [u8; MAX_PERIOD as usize]) {
+        let n = self.n;
+        let mut mask = index_mask(n as u32);
+        for (m, out) in (1..=n).rev().zip(&mut buf[..n as usize]) {
+            let idx_mspace = fastmap32(self.rng.next_u32(), m as u32) as u8;
+            let idx_nspace = select64(idx_mspace, mask);
+            mask &= !(1_u64 << (idx_nspace as u32));
+            *out = idx_nspace;
+        }
+        self.unchosen_mask = mask;
+        self.m = 0;
+    }
+}
+
+//////////////
+// NoRepeat //
+//////////////
+
+/// Wraps a permuter so that no index is ever output twice in a row, even
+/// across a period boundary, i.e., the first index of a period never equals
+/// the last index output before it.
+///
+/// Each period whose first index would repeat is thrown away and redrawn from
+/// the inner permuter, which takes `n / (n - 1)` periods on average. So if the
+/// inner permuter's periods are independent and uniform, each period is
+/// uniform over the `(n - 1) * (n - 1)!` permutations that don't start with
+/// the previous output. The first period is unconstrained. In general, each
+/// period follows the inner permuter's distribution conditioned on not
+/// starting with the previous output.
+///
+/// Needs a period of at least 2, and an inner permuter that can start a period
+/// with at least two different indices. Otherwise, e.g., for a [`Sattolo`]
+/// with period 2, or a [`WeightedPermuter`](crate::weighted::WeightedPermuter)
+/// with one non-zero weight, every period starts with the same index, and once
+/// a `reset` leaves that as the last output, no period could ever be accepted.
+/// So rather than redraw forever, `next_index` panics once `2^16` periods in a
+/// row start with the last output, which a uniform inner permuter does with
+/// probability at most `2^-65536`.
+pub struct NoRepeat<P> {
+    inner: P,
+    idxs: [u8; MAX_PERIOD as usize],
+    idx: u8,
+    last: Option<u8>,
+}
+
+// How many periods `NoRepeat` draws before giving up on the inner permuter.
+const MAX_NO_REPEAT_REDRAWS: u32 = 1 << 16;
+
+impl<P: SmallIndexPermutations> NoRepeat<P> {
+    pub fn new(inner: P) -> Self {
+        let n = inner.period();
+        assert!(n > 1);
+        Self {
+            inner,
+            idxs: INDEXES,
+            idx: n,
+            last: None,
+        }
+    }
+
+    pub fn into_inner(self) -> P {
+        self.inner
+    }
+
+    fn next_period(&mut self) {
+        for _ in 0..MAX_NO_REPEAT_REDRAWS {
+            self.inner.fill_period(&mut self.idxs);
+            if Some(self.idxs[0]) != self.last {
+                self.idx = 0;
+                return;
+            }
+        }
+        panic!(
+            "NoRepeat: {} periods in a row started with the last output {:?}; \
+             the inner permuter can't start a period with anything else",
+            MAX_NO_REPEAT_REDRAWS, self.last
+        );
+    }
+}
+
+impl<P: SmallIndexPermutations> SmallIndexPermutations for NoRepeat<P> {
+    #[inline]
+    fn period(&self) -> u8 {
+        self.inner.period()
+    }
+
+    // draw the next period lazily, since it depends on the last index output.
+    #[inline]
+    fn reset(&mut self) {
+        self.idx = self.period();
+    }
+
+    fn next_index(&mut self) -> u8 {
+        if self.idx == self.period() {
+            self.next_period();
+        }
+        let r = unsafe { *self.idxs.get_unchecked(self.idx as usize) };
+        self.idx += 1;
+        self.last = Some(r);
+        r
+    }
+}
+
+//////////////////
+// AutoPermuter //
+//////////////////
+
+/// The permuter to use when you'd rather not pick one. Currently, that's
+/// always a [`ShuffleArrayIncremental`], whatever the period, access pattern,
+/// or CPU.
+///
+/// At `n = 64` in `benches/results.i7-8700K-3.70GHz.md`, it's the fastest in
+/// every group but whole periods, where it's within 1% of `BitScatter`
+/// (119.4 ns vs 118.3 ns), e.g., 1.53 ns vs 2.26 ns for a `reset` and one
+/// index. `BitScatter` also needs a fast `pdep`, without which `select64` is
+/// over 10x slower. Other periods haven't been measured, so the choice may
+/// change if they show a better one. Construct a permuter directly to pin it.
+pub struct AutoPermuter<R>(ShuffleArrayIncremental<R>);
+
+impl<R: Rng> AutoPermuter<R> {
+    pub fn new(rng: R, n: u8) -> Self {
+        Self(ShuffleArrayIncremental::new(rng, n))
+    }
+}
+
+impl<R: Rng> SmallIndexPermutations for AutoPermuter<R> {
+    #[inline]
+    fn period(&self) -> u8 {
+        self.0.period()
+    }
+
+    #[inline]
+    fn next_index(&mut self) -> u8 {
+        self.0.next_index()
+    }
+
+    #[inline]
+    fn reset(&mut self) {
+        self.0.reset()
+    }
+
+    #[inline]
+    fn fill(&mut self, buf: &mut [u8]) -> usize {
+        self.0.fill(buf)
+    }
+
+    #[inline]
+    fn fill_period(&mut self, buf: &mut [u8; MAX_PERIOD as usize]) {
+        self.0.fill_period(buf)
+    }
 }
 
 #[inline(always)]
@@ -319,8 +831,16 @@ pub fn fill_mask(idxs: impl Iterator<Item = u8>) -> (u64, u8) {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::{
+        atomic::AtomicBitScatter,
+        lanes::{BitScatterX8, LANES},
+        lehmer::{LehmerPermuter, MAX_LEHMER_PERIOD},
+        perm::Perm64,
+        weighted::WeightedPermuter,
+    };
     use proptest::prelude::*;
     use rand::{rngs::SmallRng, SeedableRng};
+    use std::collections::HashMap;
 
     pub fn arb_small_rng() -> impl Strategy<Value = SmallRng> {
         any::<u64>().prop_map(SmallRng::seed_from_u64).no_shrink()
@@ -338,6 +858,34 @@ mod test {
         assert_eq!(mask, expected_mask);
     }
 
+    // `fill` and `fill_period` must produce exactly the same stream as calling
+    // `next_index` on an identically seeded permuter.
+    fn assert_fill_eq_next_index<P: SmallIndexPermutations>(
+        mut fill: P,
+        mut next: P,
+        skip: usize,
+        len: usize,
+    ) {
+        for _ in 0..skip {
+            assert_eq!(next.next_index(), fill.next_index());
+        }
+
+        let mut buf = vec![0_u8; len];
+        assert_eq!(len, fill.fill(&mut buf));
+        let expected = (0..len).map(|_| next.next_index()).collect::<Vec<_>>();
+        assert_eq!(expected, buf);
+
+        let n = next.period() as usize;
+        let mut buf = [0xff_u8; MAX_PERIOD as usize];
+        fill.fill_period(&mut buf);
+        let expected = next.iter_period().collect::<Vec<_>>();
+        assert_eq!(&expected[..], &buf[..n]);
+        assert!(buf[n..].iter().all(|&idx| idx == 0xff));
+
+        // both should still be in lockstep afterwards
+        assert_eq!(next.next_index(), fill.next_index());
+    }
+
     #[test]
     fn test_index_mask() {
         assert_eq!(0b0000_0001, index_mask(1));
@@ -377,6 +925,401 @@ mod test {
         assert_permutation(n, idxs);
     }
 
+    // Count how often each relative permutation `d` (where
+    // `period_k[i] == period_{k-1}[d[i]]`) shows up over `num_periods`.
+    fn relative_perm_counts(
+        mut perm: impl SmallIndexPermutations,
+        num_periods: usize,
+        relative: bool,
+    ) -> HashMap<Vec<u8>, usize> {
+        let n = perm.period();
+        let mut counts = HashMap::new();
+        let mut prev = Perm64::identity(n);
+        for _ in 0..num_periods {
+            let cur = Perm64::from_period(&mut perm);
+            let d = if relative {
+                prev.inverse().compose(&cur)
+            } else {
+                cur
+            };
+            *counts.entry(d.as_slice().to_vec()).or_default() += 1;
+            prev = cur;
+        }
+        counts
+    }
+
+    fn assert_roughly_uniform(counts: &HashMap<Vec<u8>, usize>, num_outcomes: usize) {
+        assert_eq!(num_outcomes, counts.len());
+        let total = counts.values().sum::<usize>();
+        let expected = total / num_outcomes;
+        for (d, &count) in counts {
+            assert!(
+                count.abs_diff(expected) < expected / 20,
+                "{:?}: {} vs expected {}",
+                d,
+                count,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_no_repeat_uniform() {
+        // for each previous output, each of the (3 - 1) * (3 - 1)! == 4
+        // permutations not starting with it should be equally likely.
+        let mut no_repeat = NoRepeat::new(ShuffleArrayIncremental::new(small_rng(), 3));
+        let mut counts = HashMap::<Vec<u8>, usize>::new();
+        let mut last = no_repeat.iter_period().last().unwrap();
+        for _ in 0..120_000 {
+            let mut key = vec![last];
+            key.extend(no_repeat.iter().take(3));
+            last = key[3];
+            *counts.entry(key).or_default() += 1;
+        }
+        assert!(counts.keys().all(|key| key[0] != key[1]));
+        assert_roughly_uniform(&counts, 3 * 4);
+    }
+
+    // Every period of these inners starts with 1, so once a `reset` leaves 1
+    // as the last output, no period is acceptable.
+    #[test]
+    #[should_panic(expected = "can't start a period with anything else")]
+    fn test_no_repeat_sattolo_period_2() {
+        let mut no_repeat = NoRepeat::new(Sattolo::new(small_rng(), 2));
+        // without a `reset`, each period ends with 0, so the next can start
+        assert_eq!(
+            vec![1, 0, 1, 0],
+            no_repeat.iter().take(4).collect::<Vec<_>>()
+        );
+        no_repeat.next_index();
+        no_repeat.reset();
+        no_repeat.next_index();
+    }
+
+    #[test]
+    #[should_panic(expected = "can't start a period with anything else")]
+    fn test_no_repeat_one_nonzero_weight() {
+        let mut no_repeat = NoRepeat::new(WeightedPermuter::new(small_rng(), &[0, 1]));
+        assert_eq!(1, no_repeat.next_index());
+        no_repeat.reset();
+        no_repeat.next_index();
+    }
+
+    #[test]
+    fn test_shuffle_bag_uniform() {
+        // 4! / (2! * 1! * 1!) == 12 distinct orders of the bag [0, 0, 1, 2]
+        let mut bag = ShuffleBag::new(small_rng(), &[2, 1, 1]);
+        let mut counts = HashMap::<Vec<u8>, usize>::new();
+        for _ in 0..120_000 {
+            *counts.entry(bag.iter_period().collect()).or_default() += 1;
+        }
+        assert_roughly_uniform(&counts, 12);
+    }
+
+    #[test]
+    fn test_sattolo_uniform() {
+        // (4 - 1)! single 4-cycles
+        let counts = relative_perm_counts(Sattolo::new(small_rng(), 4), 60_000, false);
+        assert_roughly_uniform(&counts, 6);
+    }
+
+    #[test]
+    fn test_derangement_uniform() {
+        // !4 == 9 derangements of 4 elements
+        let counts = relative_perm_counts(Derangement::new(small_rng(), 4), 90_000, true);
+        assert_roughly_uniform(&counts, 9);
+    }
+
+    // An operation for the model-based tests below.
+    #[derive(Clone, Debug)]
+    enum Op {
+        NextIndex,
+        Reset,
+        IterPeriod,
+        Fill(usize),
+        FillPeriod,
+    }
+
+    fn arb_ops() -> impl Strategy<Value = Vec<Op>> {
+        let op = prop_oneof![
+            4 => Just(Op::NextIndex),
+            1 => Just(Op::Reset),
+            1 => Just(Op::IterPeriod),
+            1 => (0..(2 * MAX_PERIOD as usize)).prop_map(Op::Fill),
+            1 => Just(Op::FillPeriod),
+        ];
+        proptest::collection::vec(op, 0..64)
+    }
+
+    // What a permuter guarantees on top of each period being a permutation.
+    #[derive(Clone, Copy, PartialEq, Eq)]
+    enum Constraint {
+        None,
+        NoRepeat,
+        SingleCycle,
+        Derangement,
+    }
+
+    // A reference model of a permuter, fed every index it outputs and every
+    // `reset`, that checks the output against what the permuter promises.
+    struct Model {
+        // how many times each index is output per period
+        counts: Vec<u8>,
+        constraint: Constraint,
+        period: Vec<u8>,
+        // the permuter's whole current period, if an invariant can see it, so
+        // a `reset` that abandons the period part way knows all of it
+        full_period: Option<Vec<u8>>,
+        // the previous period, or only as much of it as was output before a
+        // `reset` if `full_period` isn't known
+        prev_period: Vec<u8>,
+        last: Option<u8>,
+    }
+
+    impl Model {
+        fn new(n: u8, constraint: Constraint) -> Self {
+            Self::with_counts(vec![1; n as usize], constraint)
+        }
+
+        fn with_counts(counts: Vec<u8>, constraint: Constraint) -> Self {
+            Self {
+                counts,
+                constraint,
+                period: Vec::new(),
+                full_period: None,
+                // the first derangement is relative to the identity
+                prev_period: (0..MAX_PERIOD).collect(),
+                last: None,
+            }
+        }
+
+        fn period_len(&self) -> usize {
+            self.counts.iter().map(|&count| count as usize).sum()
+        }
+
+        fn reset(&mut self) {
+            let period = std::mem::take(&mut self.period);
+            self.prev_period = self.full_period.take().unwrap_or(period);
+        }
+
+        fn observe(&mut self, idx: u8) {
+            if self.period.len() == self.period_len() {
+                // the output is the whole period, and `full_period` may be
+                // from a period earlier in the same op
+                self.full_period = None;
+                self.reset();
+            }
+
+            let count = self.counts.get(idx as usize).copied().unwrap_or(0);
+            let seen = self.period.iter().filter(|&&i| i == idx).count();
+            assert!(
+                seen < count as usize,
+                "{} repeated before the period completed: {:?}",
+                idx,
+                self.period
+            );
+            match self.constraint {
+                Constraint::NoRepeat => assert_ne!(Some(idx), self.last),
+                Constraint::Derangement => {
+                    assert_ne!(Some(&idx), self.prev_period.get(self.period.len()))
+                }
+                Constraint::None | Constraint::SingleCycle => {}
+            }
+            self.period.push(idx);
+            self.last = Some(idx);
+
+            if self.constraint == Constraint::SingleCycle && self.period.len() == self.period_len()
+            {
+                let perm = Perm64::from_slice(&self.period).unwrap();
+                assert_eq!(1, perm.cycles().len());
+            }
+        }
+    }
+
+    // Something `Op`s can run against: a permuter, or a shuffle bag, which only
+    // has `next_item`, `reset`, and `iter_period`.
+    trait ModelTarget {
+        fn period(&self) -> u8;
+
+        // Apply `op`, returning everything it output.
+        fn apply(&mut self, op: &Op) -> Vec<u8>;
+    }
+
+    impl<P: SmallIndexPermutations> ModelTarget for P {
+        fn period(&self) -> u8 {
+            SmallIndexPermutations::period(self)
+        }
+
+        fn apply(&mut self, op: &Op) -> Vec<u8> {
+            match *op {
+                Op::NextIndex => vec![self.next_index()],
+                Op::Reset => {
+                    self.reset();
+                    Vec::new()
+                }
+                Op::IterPeriod => self.iter_period().collect(),
+                Op::Fill(len) => {
+                    let mut buf = vec![0_u8; len];
+                    assert_eq!(len, self.fill(&mut buf));
+                    buf
+                }
+                Op::FillPeriod => {
+                    let mut buf = [0_u8; MAX_PERIOD as usize];
+                    self.fill_period(&mut buf);
+                    buf[..ModelTarget::period(self) as usize].to_vec()
+                }
+            }
+        }
+    }
+
+    impl<R: rand::Rng> ModelTarget for ShuffleBag<R> {
+        fn period(&self) -> u8 {
+            ShuffleBag::period(self)
+        }
+
+        fn apply(&mut self, op: &Op) -> Vec<u8> {
+            match *op {
+                Op::NextIndex => vec![self.next_item()],
+                Op::Reset => {
+                    self.reset();
+                    Vec::new()
+                }
+                Op::IterPeriod | Op::FillPeriod => self.iter_period().collect(),
+                Op::Fill(len) => (0..len).map(|_| self.next_item()).collect(),
+            }
+        }
+    }
+
+    // `AtomicBitScatter` used from a single thread, with its own rng.
+    struct SingleThreadAtomic<R> {
+        bit_scatter: AtomicBitScatter,
+        rng: R,
+    }
+
+    impl<R: rand::Rng> ModelTarget for SingleThreadAtomic<R> {
+        fn period(&self) -> u8 {
+            self.bit_scatter.period()
+        }
+
+        fn apply(&mut self, op: &Op) -> Vec<u8> {
+            let Self { bit_scatter, rng } = self;
+            match *op {
+                Op::NextIndex => vec![bit_scatter.next_index(rng)],
+                Op::Reset => {
+                    bit_scatter.reset();
+                    Vec::new()
+                }
+                // `try_next_index` drains exactly one fresh period
+                Op::IterPeriod | Op::FillPeriod => {
+                    bit_scatter.reset();
+                    std::iter::from_fn(|| bit_scatter.try_next_index(rng)).collect()
+                }
+                // `try_next_index` until the period runs out, then `next_index`
+                // starts the next one
+                Op::Fill(len) => (0..len)
+                    .map(|_| {
+                        bit_scatter.try_next_index(rng).unwrap_or_else(|| {
+                            assert_eq!(0, bit_scatter.remaining());
+                            bit_scatter.next_index(rng)
+                        })
+                    })
+                    .collect(),
+            }
+        }
+    }
+
+    // Apply `ops` to both `perm` and `model`, checking `invariant` on the
+    // permuter's internal state after each one. `invariant` may also tell the
+    // model what it can see of that state.
+    fn run_ops<P: ModelTarget>(
+        mut perm: P,
+        mut model: Model,
+        ops: &[Op],
+        invariant: impl Fn(&P, &mut Model),
+    ) {
+        assert_eq!(model.period_len(), perm.period() as usize);
+        invariant(&perm, &mut model);
+
+        for op in ops {
+            let idxs = perm.apply(op);
+            let new_period = matches!(op, Op::Reset | Op::IterPeriod | Op::FillPeriod);
+            if new_period {
+                model.reset();
+            }
+            idxs.into_iter().for_each(|idx| model.observe(idx));
+            if new_period && !matches!(op, Op::Reset) {
+                assert_eq!(model.period_len(), model.period.len());
+            }
+            invariant(&perm, &mut model);
+        }
+    }
+
+    // Like `run_ops`, for `BitScatterX8`, with a model per lane.
+    fn run_x8_ops<R: rand::Rng>(mut x8: BitScatterX8<R>, ops: &[Op]) {
+        let n = x8.period();
+        let mut models = (0..LANES)
+            .map(|_| Model::new(n, Constraint::None))
+            .collect::<Vec<_>>();
+
+        for op in ops {
+            let num_idxs = match *op {
+                Op::NextIndex => 1,
+                Op::Reset => 0,
+                Op::IterPeriod | Op::FillPeriod => n as usize,
+                Op::Fill(len) => len,
+            };
+            if !matches!(op, Op::NextIndex | Op::Fill(_)) {
+                x8.reset();
+                models.iter_mut().for_each(Model::reset);
+            }
+            for _ in 0..num_idxs {
+                for (model, idx) in models.iter_mut().zip(x8.next_indices()) {
+                    model.observe(idx);
+                }
+            }
+        }
+    }
+
+    fn no_invariant<P>(_perm: &P, _model: &mut Model) {}
+
+    // Run `ops` against `NoRepeat` wrapping `perm`.
+    fn run_no_repeat_ops<P: SmallIndexPermutations>(perm: P, ops: &[Op]) {
+        let model = Model::new(perm.period(), Constraint::NoRepeat);
+        run_ops(NoRepeat::new(perm), model, ops, no_invariant);
+    }
+
+    fn bit_scatter_invariant<R>(bit_scatter: &BitScatter<R>, model: &mut Model) {
+        assert_eq!(bit_scatter.m as u32, bit_scatter.unchosen_mask.count_ones());
+        let (chosen, _) = fill_mask(model.period.iter().copied());
+        let expected_unchosen = index_mask(bit_scatter.n as u32) & !chosen;
+        assert_eq!(expected_unchosen, bit_scatter.unchosen_mask);
+    }
+
+    fn shuffle_array_incremental_invariant<R>(
+        shuffle: &ShuffleArrayIncremental<R>,
+        model: &mut Model,
+    ) {
+        // the output so far is the prefix, the rest are still to be chosen
+        let n = shuffle.n as usize;
+        assert_permutation(shuffle.n, shuffle.idxs[..n].iter().copied());
+        let pos = model.period.len() % n;
+        assert_eq!(pos, shuffle.idx as usize);
+        assert_eq!(&model.period[..pos], &shuffle.idxs[..pos]);
+    }
+
+    fn derangement_invariant<R>(derangement: &Derangement<R>, model: &mut Model) {
+        // the output so far is a prefix of the current period. A `reset` draws
+        // the next period against all of this one, not just the output part.
+        let period = &derangement.idxs[..derangement.n as usize];
+        assert_eq!(&model.period[..], &period[..model.period.len()]);
+        model.full_period = Some(period.to_vec());
+    }
+
+    fn atomic_invariant<R>(atomic: &SingleThreadAtomic<R>, model: &mut Model) {
+        let remaining = model.period_len() - model.period.len();
+        assert_eq!(remaining, atomic.bit_scatter.remaining() as usize);
+    }
+
     proptest! {
         #![proptest_config(ProptestConfig::with_cases(10000))]
 
@@ -386,7 +1329,7 @@ mod test {
             let mut idxs = shuffle.iter_period().collect::<Vec<_>>();
             idxs.sort_unstable();
 
-            let expected_idxs = (0..n).into_iter().collect::<Vec<_>>();
+            let expected_idxs = (0..n).collect::<Vec<_>>();
             assert_eq!(expected_idxs, idxs);
         }
 
@@ -402,7 +1345,7 @@ mod test {
             let mut idxs = shuffle.iter_period().collect::<Vec<_>>();
             idxs.sort_unstable();
 
-            let expected_idxs = (0..n).into_iter().collect::<Vec<_>>();
+            let expected_idxs = (0..n).collect::<Vec<_>>();
             assert_eq!(expected_idxs, idxs);
         }
 
@@ -418,7 +1361,7 @@ mod test {
             let mut idxs = shuffle.iter_period().collect::<Vec<_>>();
             idxs.sort_unstable();
 
-            let expected_idxs = (0..n).into_iter().collect::<Vec<_>>();
+            let expected_idxs = (0..n).collect::<Vec<_>>();
             assert_eq!(expected_idxs, idxs);
         }
 
@@ -434,14 +1377,182 @@ mod test {
             let mut idxs = bit_scatter.iter_period().collect::<Vec<_>>();
             idxs.sort_unstable();
 
-            let expected_idxs = (0..n).into_iter().collect::<Vec<_>>();
+            let expected_idxs = (0..n).collect::<Vec<_>>();
             assert_eq!(expected_idxs, idxs);
         }
 
+        #[test]
+        fn sattolo_is_single_cycle(n in 1..=MAX_PERIOD, rng in arb_small_rng()) {
+            let mut sattolo = Sattolo::new(rng, n);
+            for _ in 0..3 {
+                let perm = Perm64::from_period(&mut sattolo);
+                assert_eq!(1, perm.cycles().len());
+            }
+            // also via `next_index`, partway through a period
+            sattolo.next_index();
+            assert_permutation(n, sattolo.iter_period());
+        }
+
+        #[test]
+        fn derangement_moves_every_index(n in 2..=MAX_PERIOD, rng in arb_small_rng()) {
+            let mut derangement = Derangement::new(rng, n);
+            let mut prev = INDEXES;
+            for _ in 0..3 {
+                let cur = derangement.iter_period().collect::<Vec<_>>();
+                assert_permutation(n, cur.iter().copied());
+                assert!(cur.iter().zip(&prev).all(|(a, b)| a != b));
+                prev[..n as usize].copy_from_slice(&cur);
+            }
+        }
+
+        #[test]
+        fn no_repeat_never_repeats(
+            n in 2..=MAX_PERIOD,
+            resets in proptest::collection::vec(0..(2 * MAX_PERIOD as usize), 0..8),
+            rng in arb_small_rng(),
+        ) {
+            let mut no_repeat = NoRepeat::new(BitScatter::new(rng, n));
+            let mut idxs = Vec::new();
+            for take in resets {
+                // each chunk is a prefix of a fresh period, possibly spanning
+                // several periods
+                idxs.extend(no_repeat.iter().take(take));
+            }
+            assert!(idxs.windows(2).all(|w| w[0] != w[1]));
+
+            let mut periods = no_repeat.into_inner();
+            assert_permutation(n, periods.iter_period());
+        }
+
+        #[test]
+        fn shuffle_bag_period_has_counts(
+            // at most 32 items of at most 2 each keeps the period <= MAX_PERIOD
+            counts in proptest::collection::vec(0..=2_u8, 1..=32)
+                .prop_filter("empty bag", |counts| counts.iter().any(|&c| c > 0)),
+            rng in arb_small_rng(),
+        ) {
+            let mut bag = ShuffleBag::new(rng, &counts);
+            let total = counts.iter().sum::<u8>();
+            assert_eq!(total, bag.period());
+            for _ in 0..3 {
+                let mut period_counts = vec![0_u8; counts.len()];
+                for item in bag.iter_period() {
+                    period_counts[item as usize] += 1;
+                }
+                assert_eq!(counts, period_counts);
+            }
+        }
+
+        #[test]
+        fn auto_permuter_is_permutation(n in 1..=MAX_PERIOD, rng in arb_small_rng()) {
+            let mut auto = AutoPermuter::new(rng, n);
+            assert_permutation(n, auto.iter_period());
+        }
+
+        #[test]
+        fn fill_eq_next_index(
+            n in 1..=MAX_PERIOD,
+            skip in 0..(2 * MAX_PERIOD as usize),
+            len in 0..(3 * MAX_PERIOD as usize),
+            rng in arb_small_rng(),
+        ) {
+            assert_fill_eq_next_index(Shuffle::new(rng.clone(), n), Shuffle::new(rng.clone(), n), skip, len);
+            assert_fill_eq_next_index(ShuffleArray::new(rng.clone(), n), ShuffleArray::new(rng.clone(), n), skip, len);
+            assert_fill_eq_next_index(
+                ShuffleArrayIncremental::new(rng.clone(), n),
+                ShuffleArrayIncremental::new(rng.clone(), n),
+                skip,
+                len,
+            );
+            assert_fill_eq_next_index(BitScatter::new(rng.clone(), n), BitScatter::new(rng, n), skip, len);
+        }
+
         #[test]
         fn bit_scatter_is_permutation(n in 1..=MAX_PERIOD, rng in arb_small_rng()) {
             let mut bit_scatter = BitScatter::new(rng, n);
             assert_permutation(n, bit_scatter.iter_period());
         }
     }
+
+    proptest! {
+        // each case runs every permuter, so fewer cases than above
+        #![proptest_config(ProptestConfig::with_cases(1000))]
+
+        #[test]
+        fn permuters_match_model(
+            n in 1..=MAX_PERIOD,
+            ops in arb_ops(),
+            weights in proptest::collection::vec(0..4_u32, 1..=MAX_PERIOD as usize),
+            rng in arb_small_rng(),
+        ) {
+            let model = |constraint| Model::new(n, constraint);
+            run_ops(Shuffle::new(rng.clone(), n), model(Constraint::None), &ops, no_invariant);
+            run_ops(ShuffleArray::new(rng.clone(), n), model(Constraint::None), &ops, no_invariant);
+            run_ops(
+                ShuffleArrayIncremental::new(rng.clone(), n),
+                model(Constraint::None),
+                &ops,
+                shuffle_array_incremental_invariant,
+            );
+            run_ops(BitScatter::new(rng.clone(), n), model(Constraint::None), &ops, bit_scatter_invariant);
+            run_ops(Sattolo::new(rng.clone(), n), model(Constraint::SingleCycle), &ops, no_invariant);
+            run_ops(
+                AutoPermuter::new(rng.clone(), n),
+                model(Constraint::None),
+                &ops,
+                |auto, model| shuffle_array_incremental_invariant(&auto.0, model),
+            );
+            if n <= MAX_LEHMER_PERIOD {
+                run_ops(LehmerPermuter::new(rng.clone(), n), model(Constraint::None), &ops, no_invariant);
+            }
+            if n >= 2 {
+                run_ops(
+                    Derangement::new(rng.clone(), n),
+                    model(Constraint::Derangement),
+                    &ops,
+                    derangement_invariant,
+                );
+                run_no_repeat_ops(Shuffle::new(rng.clone(), n), &ops);
+                run_no_repeat_ops(ShuffleArray::new(rng.clone(), n), &ops);
+                run_no_repeat_ops(ShuffleArrayIncremental::new(rng.clone(), n), &ops);
+                run_no_repeat_ops(BitScatter::new(rng.clone(), n), &ops);
+                // `NoRepeat` panics on `Sattolo` with `n == 2`; see
+                // `test_no_repeat_sattolo_period_2`
+                if n >= 3 {
+                    run_no_repeat_ops(Sattolo::new(rng.clone(), n), &ops);
+                }
+                run_no_repeat_ops(Derangement::new(rng.clone(), n), &ops);
+                run_no_repeat_ops(AutoPermuter::new(rng.clone(), n), &ops);
+                if n <= MAX_LEHMER_PERIOD {
+                    run_no_repeat_ops(LehmerPermuter::new(rng.clone(), n), &ops);
+                }
+            }
+            let atomic = SingleThreadAtomic {
+                bit_scatter: AtomicBitScatter::new(n),
+                rng: rng.clone(),
+            };
+            run_ops(atomic, model(Constraint::None), &ops, atomic_invariant);
+            run_x8_ops(BitScatterX8::new(rng.clone(), n), &ops);
+
+            // `NoRepeat` panics on a single non-zero weight; see
+            // `test_no_repeat_one_nonzero_weight`
+            if weights.iter().filter(|&&weight| weight > 0).count() >= 2 {
+                run_no_repeat_ops(WeightedPermuter::new(rng.clone(), &weights), &ops);
+            }
+            let weighted = WeightedPermuter::new(rng, &weights);
+            let model = Model::new(weights.len() as u8, Constraint::None);
+            run_ops(weighted, model, &ops, no_invariant);
+        }
+
+        #[test]
+        fn shuffle_bag_matches_model(
+            counts in proptest::collection::vec(0..=2_u8, 1..=32)
+                .prop_filter("empty bag", |counts| counts.iter().any(|&c| c > 0)),
+            ops in arb_ops(),
+            rng in arb_small_rng(),
+        ) {
+            let bag = ShuffleBag::new(rng, &counts);
+            run_ops(bag, Model::with_counts(counts, Constraint::None), &ops, no_invariant);
+        }
+    }
 }
@@ -0,0 +1,338 @@
+use crate::{index_mask, SmallIndexPermutations, INDEXES, MAX_PERIOD};
+use std::fmt;
+
+/// A permutation of `[0, n)` for `0 < n <= MAX_PERIOD`, stored inline.
+///
+/// `perm[i]` is the `i`'th index produced in a period, so a `Perm64` captured
+/// from a permuter (see [`Perm64::from_period`]) reads back in the same order.
+/// As a function, the permutation maps `i` to `perm[i]`.
+///
+/// ## Example
+///
+/// ```rust
+/// use fastperm::{perm::Perm64, BitScatter};
+/// use rand::{rngs::SmallRng, SeedableRng};
+///
+/// let mut bit_scatter = BitScatter::new(SmallRng::seed_from_u64(123), 10);
+/// let perm = Perm64::from_period(&mut bit_scatter);
+///
+/// assert!(perm.compose(&perm.inverse()).is_identity());
+/// assert!(perm.pow(perm.order() as i64).is_identity());
+/// ```
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Perm64 {
+    idxs: [u8; MAX_PERIOD as usize],
+    n: u8,
+}
+
+impl Perm64 {
+    /// The identity permutation on `[0, n)`.
+    pub fn identity(n: u8) -> Self {
+        assert!(n <= MAX_PERIOD && n > 0);
+        Self { idxs: INDEXES, n }
+    }
+
+    /// Capture one whole period of `perm` (starting a new period).
+    ///
+    /// Panics if the period isn't a permutation of `[0, perm.period())`, which
+    /// means `perm` breaks the `SmallIndexPermutations` contract.
+    pub fn from_period<P: SmallIndexPermutations + ?Sized>(perm: &mut P) -> Self {
+        let mut idxs = INDEXES;
+        perm.fill_period(&mut idxs);
+        let n = perm.period() as usize;
+        Self::from_slice(&idxs[..n]).expect("period isn't a permutation")
+    }
+
+    /// Returns `None` if `idxs` isn't a permutation of `[0, idxs.len())`, or
+    /// if it's empty or longer than `MAX_PERIOD`.
+    pub fn from_slice(idxs: &[u8]) -> Option<Self> {
+        let n = idxs.len();
+        if n == 0 || n > MAX_PERIOD as usize {
+            return None;
+        }
+        let mut seen = 0_u64;
+        for &idx in idxs {
+            if idx as usize >= n || seen & (1 << idx) != 0 {
+                return None;
+            }
+            seen |= 1 << idx;
+        }
+        let mut perm = Self::identity(n as u8);
+        perm.idxs[..n].copy_from_slice(idxs);
+        Some(perm)
+    }
+
+    #[inline(always)]
+    pub fn period(&self) -> u8 {
+        self.n
+    }
+
+    #[inline(always)]
+    pub fn as_slice(&self) -> &[u8] {
+        &self.idxs[..self.n as usize]
+    }
+
+    /// Where the permutation sends `i`.
+    #[inline]
+    pub fn get(&self, i: u8) -> u8 {
+        self.as_slice()[i as usize]
+    }
+
+    pub fn is_identity(&self) -> bool {
+        self.as_slice() == &INDEXES[..self.n as usize]
+    }
+
+    /// The composition `self ∘ other`, i.e., the permutation mapping `i` to
+    /// `self[other[i]]`.
+    pub fn compose(&self, other: &Self) -> Self {
+        assert_eq!(self.n, other.n);
+        let mut out = Self::identity(self.n);
+        for (out, &idx) in out.idxs.iter_mut().zip(other.as_slice()) {
+            *out = self.idxs[idx as usize];
+        }
+        out
+    }
+
+    pub fn inverse(&self) -> Self {
+        let mut out = Self::identity(self.n);
+        for (i, &idx) in self.as_slice().iter().enumerate() {
+            out.idxs[idx as usize] = i as u8;
+        }
+        out
+    }
+
+    /// `self` composed with itself `k` times. Negative `k` raises the inverse.
+    pub fn pow(&self, k: i64) -> Self {
+        let mut base = if k < 0 { self.inverse() } else { *self };
+        // no need to go around any cycle more than once
+        let mut k = k.unsigned_abs() % self.order();
+        let mut out = Self::identity(self.n);
+        while k > 0 {
+            if k & 1 == 1 {
+                out = out.compose(&base);
+            }
+            base = base.compose(&base);
+            k >>= 1;
+        }
+        out
+    }
+
+    /// The cycle decomposition, including fixed points as 1-cycles. Each
+    /// cycle starts with its smallest element, and the cycles are ordered by
+    /// their first element.
+    pub fn cycles(&self) -> Vec<Vec<u8>> {
+        let mut cycles = Vec::new();
+        let mut unvisited = index_mask(self.n as u32);
+        while unvisited != 0 {
+            let start = unvisited.trailing_zeros() as u8;
+            let mut cycle = Vec::new();
+            let mut i = start;
+            loop {
+                unvisited &= !(1 << i);
+                cycle.push(i);
+                i = self.idxs[i as usize];
+                if i == start {
+                    break;
+                }
+            }
+            cycles.push(cycle);
+        }
+        cycles
+    }
+
+    fn cycle_lens(&self) -> impl Iterator<Item = u64> + '_ {
+        let mut unvisited = index_mask(self.n as u32);
+        std::iter::from_fn(move || {
+            if unvisited == 0 {
+                return None;
+            }
+            let start = unvisited.trailing_zeros() as u8;
+            let mut len = 0;
+            let mut i = start;
+            loop {
+                unvisited &= !(1 << i);
+                len += 1;
+                i = self.idxs[i as usize];
+                if i == start {
+                    return Some(len);
+                }
+            }
+        })
+    }
+
+    /// `1` for even permutations and `-1` for odd permutations.
+    pub fn sign(&self) -> i8 {
+        // each k-cycle is a product of k - 1 transpositions
+        let num_cycles = self.cycle_lens().count() as u8;
+        if (self.n - num_cycles) & 1 == 0 {
+            1
+        } else {
+            -1
+        }
+    }
+
+    /// The smallest `k > 0` with `self.pow(k)` the identity, i.e., the lcm of
+    /// the cycle lengths. Fits comfortably in a `u64` for `n <= 64`.
+    pub fn order(&self) -> u64 {
+        self.cycle_lens()
+            .fold(1, |order, len| order / gcd(order, len) * len)
+    }
+
+    /// Reorder `slice` in place so that `slice[i]` becomes the old
+    /// `slice[self[i]]`, i.e., the items end up in the order this permutation
+    /// visits them.
+    ///
+    /// Panics if `slice.len() != self.period()`.
+    pub fn apply_to_slice<T>(&self, slice: &mut [T]) {
+        assert_eq!(slice.len(), self.n as usize);
+        let mut unvisited = index_mask(self.n as u32);
+        while unvisited != 0 {
+            let start = unvisited.trailing_zeros() as u8;
+            // walk the cycle, pulling each slot's new item forward
+            let mut i = start;
+            loop {
+                unvisited &= !(1 << i);
+                let next = self.idxs[i as usize];
+                if next == start {
+                    break;
+                }
+                slice.swap(i as usize, next as usize);
+                i = next;
+            }
+        }
+    }
+}
+
+impl fmt::Debug for Perm64 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Perm64").field(&self.as_slice()).finish()
+    }
+}
+
+fn gcd(mut a: u64, mut b: u64) -> u64 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+//////////
+// Test //
+//////////
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{test::arb_small_rng, ShuffleArrayIncremental};
+    use proptest::prelude::*;
+
+    fn arb_perm64() -> impl Strategy<Value = Perm64> {
+        (1..=MAX_PERIOD, arb_small_rng()).prop_map(|(n, rng)| arb_perm64_n(n, rng))
+    }
+
+    fn arb_perm64_n(n: u8, rng: rand::rngs::SmallRng) -> Perm64 {
+        Perm64::from_period(&mut ShuffleArrayIncremental::new(rng, n))
+    }
+
+    fn arb_perm64_pair() -> impl Strategy<Value = (Perm64, Perm64)> {
+        (1..=MAX_PERIOD, arb_small_rng(), arb_small_rng())
+            .prop_map(|(n, rng1, rng2)| (arb_perm64_n(n, rng1), arb_perm64_n(n, rng2)))
+    }
+
+    #[test]
+    fn test_perm64_small() {
+        // (0 2 1)(3)(4 5)
+        let perm = Perm64::from_slice(&[2, 0, 1, 3, 5, 4]).unwrap();
+        assert_eq!(vec![vec![0, 2, 1], vec![3], vec![4, 5]], perm.cycles());
+        assert_eq!(&[1, 2, 0, 3, 5, 4], perm.inverse().as_slice());
+        assert_eq!(&[1, 2, 0, 3, 4, 5], perm.pow(2).as_slice());
+        assert_eq!(perm.inverse(), perm.pow(-1));
+        assert_eq!(-1, perm.sign());
+        assert_eq!(6, perm.order());
+
+        let mut items = ['a', 'b', 'c', 'd', 'e', 'f'];
+        perm.apply_to_slice(&mut items);
+        assert_eq!(['c', 'a', 'b', 'd', 'f', 'e'], items);
+    }
+
+    #[test]
+    fn test_perm64_from_slice_invalid() {
+        assert_eq!(None, Perm64::from_slice(&[0, 0]));
+        assert_eq!(None, Perm64::from_slice(&[1, 2]));
+        assert_eq!(None, Perm64::from_slice(&[0; MAX_PERIOD as usize + 1]));
+        assert_eq!(None, Perm64::from_slice(&[]));
+        assert!(Perm64::from_slice(&[0]).unwrap().is_identity());
+    }
+
+    // breaks the `SmallIndexPermutations` contract by outputting items
+    // outside `[0, period())`, like a shuffle bag with counts `[0, 0, 1, 1]`
+    struct OutOfRange;
+
+    impl SmallIndexPermutations for OutOfRange {
+        fn period(&self) -> u8 {
+            2
+        }
+
+        fn next_index(&mut self) -> u8 {
+            3
+        }
+
+        fn reset(&mut self) {}
+    }
+
+    #[test]
+    #[should_panic(expected = "isn't a permutation")]
+    fn test_perm64_from_period_invalid() {
+        Perm64::from_period(&mut OutOfRange);
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(2000))]
+
+        #[test]
+        fn perm64_from_period_is_permutation(perm in arb_perm64()) {
+            assert_eq!(Some(perm), Perm64::from_slice(perm.as_slice()));
+        }
+
+        #[test]
+        fn perm64_inverse_is_inverse(perm in arb_perm64()) {
+            assert!(perm.compose(&perm.inverse()).is_identity());
+            assert!(perm.inverse().compose(&perm).is_identity());
+            assert_eq!(perm, perm.inverse().inverse());
+        }
+
+        #[test]
+        fn perm64_pow_order_is_identity(perm in arb_perm64()) {
+            let order = perm.order();
+            assert!(perm.pow(order as i64).is_identity());
+            assert_eq!(perm, perm.pow(order as i64 + 1));
+            assert_eq!(perm.compose(&perm).compose(&perm), perm.pow(3));
+            assert_eq!(perm.inverse().pow(2), perm.pow(-2));
+        }
+
+        #[test]
+        fn perm64_sign_is_homomorphism((a, b) in arb_perm64_pair()) {
+            assert_eq!(a.sign() * b.sign(), a.compose(&b).sign());
+        }
+
+        #[test]
+        fn perm64_cycles_follow_perm(perm in arb_perm64()) {
+            let cycles = perm.cycles();
+            let total = cycles.iter().map(Vec::len).sum::<usize>();
+            assert_eq!(perm.period() as usize, total);
+            for cycle in cycles {
+                for (i, &idx) in cycle.iter().enumerate() {
+                    assert_eq!(cycle[(i + 1) % cycle.len()], perm.get(idx));
+                }
+            }
+        }
+
+        #[test]
+        fn perm64_apply_to_slice_gathers(perm in arb_perm64()) {
+            let mut items = (0..perm.period()).map(|i| i as u32 * 10).collect::<Vec<_>>();
+            perm.apply_to_slice(&mut items);
+            let expected = perm.as_slice().iter().map(|&idx| idx as u32 * 10).collect::<Vec<_>>();
+            assert_eq!(expected, items);
+        }
+    }
+}
@@ -49,6 +49,21 @@ pub fn select64(idx: u8, mask: u64) -> u8 {
     }
 }
 
+/// Whether [`select64`] can use the hardware `pdep` instruction on this CPU.
+/// Without it, `select64` falls back to the much slower binary search in
+/// [`select64_fallback`].
+#[inline]
+pub fn has_pdep() -> bool {
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    {
+        is_x86_feature_detected!("bmi2")
+    }
+    #[cfg(not(any(target_arch = "x86", target_arch = "x86_64")))]
+    {
+        false
+    }
+}
+
 pub fn select64_fallback(idx: u8, mask: u64) -> u8 {
     let b0 = mask;
     let b1 = (b0 & 0x5555_5555_5555_5555) + ((b0 >> 1) & 0x5555_5555_5555_5555);
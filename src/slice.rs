@@ -0,0 +1,181 @@
+use crate::{fastmap32, BitScatter, SmallIndexPermutations, MAX_PERIOD};
+use rand::Rng;
+use std::iter::{ExactSizeIterator, FusedIterator, Iterator};
+
+/// Extension trait on small slices (`len() <= MAX_PERIOD`), mirroring
+/// [`rand::seq::SliceRandom`] but backed by the permuters in this crate.
+///
+/// The methods are prefixed with `fast_` so they don't collide with
+/// `SliceRandom` when both traits are in scope.
+///
+/// ## Example
+///
+/// ```rust
+/// use fastperm::slice::FastShuffle;
+/// use rand::{rngs::SmallRng, SeedableRng};
+///
+/// let mut rng = SmallRng::seed_from_u64(123);
+/// let mut backends = vec!["a", "b", "c", "d"];
+///
+/// backends.fast_shuffle(&mut rng);
+/// assert_eq!(4, backends.random_order_iter(&mut rng).count());
+/// assert_eq!(2, backends.fast_choose_multiple(&mut rng, 2).count());
+/// ```
+pub trait FastShuffle {
+    type Item;
+
+    /// Shuffle the slice in place, using the same incremental swaps as
+    /// [`ShuffleArrayIncremental`](crate::ShuffleArrayIncremental).
+    ///
+    /// Panics if `len() > MAX_PERIOD`.
+    fn fast_shuffle<R: Rng + ?Sized>(&mut self, rng: &mut R);
+
+    /// Iterate over every element of the slice by reference in a random
+    /// order, using a [`BitScatter`] to choose the indices.
+    ///
+    /// Panics if `len() > MAX_PERIOD`.
+    fn random_order_iter<'a, R: Rng + ?Sized>(
+        &'a self,
+        rng: &'a mut R,
+    ) -> RandomOrderIter<'a, Self::Item, R>;
+
+    /// Choose `amount` distinct elements of the slice by reference in a random
+    /// order. Yields `min(amount, len())` elements.
+    ///
+    /// Panics if `len() > MAX_PERIOD`.
+    fn fast_choose_multiple<'a, R: Rng + ?Sized>(
+        &'a self,
+        rng: &'a mut R,
+        amount: usize,
+    ) -> RandomOrderIter<'a, Self::Item, R>;
+}
+
+impl<T> FastShuffle for [T] {
+    type Item = T;
+
+    fn fast_shuffle<R: Rng + ?Sized>(&mut self, rng: &mut R) {
+        let n = self.len();
+        assert!(n <= MAX_PERIOD as usize);
+        // one full period of `ShuffleArrayIncremental`, but swapping the items
+        // directly. the last swap is always a no-op, so skip it.
+        for idx in 0..n.saturating_sub(1) {
+            let idx_swap = fastmap32(rng.next_u32(), (n - idx) as u32) as usize + idx;
+            self.swap(idx, idx_swap);
+        }
+    }
+
+    #[inline]
+    fn random_order_iter<'a, R: Rng + ?Sized>(
+        &'a self,
+        rng: &'a mut R,
+    ) -> RandomOrderIter<'a, T, R> {
+        RandomOrderIter::new(self, rng, self.len())
+    }
+
+    #[inline]
+    fn fast_choose_multiple<'a, R: Rng + ?Sized>(
+        &'a self,
+        rng: &'a mut R,
+        amount: usize,
+    ) -> RandomOrderIter<'a, T, R> {
+        RandomOrderIter::new(self, rng, amount.min(self.len()))
+    }
+}
+
+/// An iterator over (a prefix of) a random permutation of a slice's elements.
+///
+/// See [`FastShuffle::random_order_iter`] and
+/// [`FastShuffle::fast_choose_multiple`].
+pub struct RandomOrderIter<'a, T, R: ?Sized> {
+    slice: &'a [T],
+    bit_scatter: BitScatter<&'a mut R>,
+    remaining: u8,
+}
+
+impl<'a, T, R: Rng + ?Sized> RandomOrderIter<'a, T, R> {
+    fn new(slice: &'a [T], rng: &'a mut R, amount: usize) -> Self {
+        let n = slice.len();
+        assert!(n <= MAX_PERIOD as usize);
+        // `BitScatter` needs a non-empty period; an empty slice just never
+        // samples from it.
+        let bit_scatter = BitScatter::new(rng, n.max(1) as u8);
+        Self {
+            slice,
+            bit_scatter,
+            remaining: amount as u8,
+        }
+    }
+}
+
+impl<'a, T, R: Rng + ?Sized> Iterator for RandomOrderIter<'a, T, R> {
+    type Item = &'a T;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        let idx = self.bit_scatter.next_index();
+        debug_assert!((idx as usize) < self.slice.len());
+        Some(unsafe { self.slice.get_unchecked(idx as usize) })
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining as usize, Some(self.remaining as usize))
+    }
+}
+
+impl<'a, T, R: Rng + ?Sized> ExactSizeIterator for RandomOrderIter<'a, T, R> {}
+
+impl<'a, T, R: Rng + ?Sized> FusedIterator for RandomOrderIter<'a, T, R> {}
+
+//////////
+// Test //
+//////////
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{fill_mask, index_mask, test::arb_small_rng};
+    use proptest::prelude::*;
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(10000))]
+
+        #[test]
+        fn fast_shuffle_is_permutation(n in 0..=MAX_PERIOD, mut rng in arb_small_rng()) {
+            let mut items = (0..n).collect::<Vec<_>>();
+            items.fast_shuffle(&mut rng);
+            items.sort_unstable();
+
+            let expected_items = (0..n).collect::<Vec<_>>();
+            assert_eq!(expected_items, items);
+        }
+
+        #[test]
+        fn random_order_iter_is_permutation(n in 0..=MAX_PERIOD, mut rng in arb_small_rng()) {
+            let items = (0..n).collect::<Vec<_>>();
+            let (mask, count) = fill_mask(items.random_order_iter(&mut rng).copied());
+            assert_eq!(n, count);
+            assert_eq!(if n == 0 { 0 } else { index_mask(n as u32) }, mask);
+        }
+
+        #[test]
+        fn fast_choose_multiple_is_distinct(
+            n in 0..=MAX_PERIOD,
+            amount in 0..=(MAX_PERIOD as usize + 8),
+            mut rng in arb_small_rng(),
+        ) {
+            let items = (0..n).collect::<Vec<_>>();
+            let iter = items.fast_choose_multiple(&mut rng, amount);
+            let expected_count = amount.min(n as usize);
+            assert_eq!(expected_count, iter.len());
+
+            let (mask, count) = fill_mask(iter.copied());
+            assert_eq!(expected_count, count as usize);
+            assert_eq!(expected_count, mask.count_ones() as usize);
+        }
+    }
+}
@@ -0,0 +1,398 @@
+//! Output stability guarantees.
+//!
+//! Driven by a [`StableRng`], the following produce exactly the same output
+//! for the same seed and arguments on every platform and in every release of
+//! this crate; changing any of them is a breaking change:
+//!
+//! * [`ShuffleArrayIncremental`](crate::ShuffleArrayIncremental),
+//!   [`BitScatter`](crate::BitScatter), [`Sattolo`](crate::Sattolo),
+//!   [`Derangement`](crate::Derangement), and [`NoRepeat`](crate::NoRepeat)
+//!   wrapping any of them, including their `fill` and `fill_period` output.
+//! * [`ShuffleBag`](crate::ShuffleBag).
+//! * [`BitScatterX8`](crate::lanes::BitScatterX8), with or without AVX2.
+//! * [`AtomicBitScatter`](crate::atomic::AtomicBitScatter) used from a single
+//!   thread.
+//! * [`FastShuffle`](crate::slice::FastShuffle) and
+//!   [`random_subset_mask`](crate::subset::random_subset_mask).
+//! * [`StableRng`] itself, and so the
+//!   [`Permuters`](crate::streams::Permuters) streams.
+//!
+//! These only consume the rng through `next_u32`, never through `rand`'s
+//! distributions, whose algorithms may change between `rand` versions. So
+//! [`Shuffle`](crate::Shuffle), [`ShuffleArray`](crate::ShuffleArray),
+//! [`LehmerPermuter`](crate::lehmer::LehmerPermuter),
+//! [`WeightedPermuter`](crate::weighted::WeightedPermuter), and
+//! [`random_subset_mask_exact`](crate::subset::random_subset_mask_exact) are
+//! _not_ covered. Neither is [`AutoPermuter`](crate::AutoPermuter), whose
+//! choice of permuter may change.
+//!
+//! `tests/golden_vectors.txt` pins the covered outputs; see the
+//! `golden_vectors_unchanged` test in this module. A separate section of it
+//! pins the uncovered ones too, with `rand` at the version in the checked-in
+//! `Cargo.lock`, so they can't change by accident either. After a `cargo
+//! update` that changes them, re-bless them only if the change is intended.
+
+use rand::{Error, RngCore, SeedableRng};
+
+/// The crate-specified rng for reproducible output: xoshiro256++, seeded from
+/// a `u64` via SplitMix64, like `rand_xoshiro::Xoshiro256PlusPlus`.
+///
+/// Unlike `rand::rngs::SmallRng`, which may change algorithm between `rand`
+/// versions and platforms, this rng's output is part of this crate's
+/// compatibility promise. See the [module docs](self).
+///
+/// ## Example
+///
+/// ```rust
+/// use fastperm::{stable::StableRng, BitScatter, SmallIndexPermutations};
+/// use rand::SeedableRng;
+///
+/// let mut bit_scatter = BitScatter::new(StableRng::seed_from_u64(7), 8);
+/// let period = bit_scatter.iter_period().collect::<Vec<_>>();
+/// // the same on every platform, forever
+/// assert_eq!(vec![0, 2, 6, 4, 7, 3, 5, 1], period);
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StableRng {
+    s: [u64; 4],
+}
+
+impl StableRng {
+    /// Advance the rng by `2^128` steps, e.g., to split it into
+    /// non-overlapping streams.
+    pub fn jump(&mut self) {
+        const JUMP: [u64; 4] = [
+            0x180e_c6d3_3cfd_0aba,
+            0xd5a6_1266_f0c9_392c,
+            0xa958_2618_e03f_c9aa,
+            0x39ab_dc45_29b1_661c,
+        ];
+
+        let mut s = [0_u64; 4];
+        for jump in JUMP {
+            for b in 0..64 {
+                if jump & (1 << b) != 0 {
+                    for (s, self_s) in s.iter_mut().zip(&self.s) {
+                        *s ^= self_s;
+                    }
+                }
+                self.next_u64();
+            }
+        }
+        self.s = s;
+    }
+}
+
+impl RngCore for StableRng {
+    #[inline]
+    fn next_u32(&mut self) -> u32 {
+        // the upper bits are the higher quality ones
+        (self.next_u64() >> 32) as u32
+    }
+
+    #[inline]
+    fn next_u64(&mut self) -> u64 {
+        let s = &mut self.s;
+        let r = s[0].wrapping_add(s[3]).rotate_left(23).wrapping_add(s[0]);
+
+        let t = s[1] << 17;
+        s[2] ^= s[0];
+        s[3] ^= s[1];
+        s[1] ^= s[2];
+        s[0] ^= s[3];
+        s[2] ^= t;
+        s[3] = s[3].rotate_left(45);
+
+        r
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(8) {
+            let bytes = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+impl SeedableRng for StableRng {
+    type Seed = [u8; 32];
+
+    /// An all-zero seed, which would get xoshiro stuck at zero, is replaced
+    /// with `seed_from_u64(0)`.
+    fn from_seed(seed: [u8; 32]) -> Self {
+        if seed == [0; 32] {
+            return Self::seed_from_u64(0);
+        }
+        let mut s = [0_u64; 4];
+        for (s, bytes) in s.iter_mut().zip(seed.chunks_exact(8)) {
+            *s = u64::from_le_bytes(bytes.try_into().unwrap());
+        }
+        Self { s }
+    }
+
+    fn seed_from_u64(seed: u64) -> Self {
+        Self::seed_from_u64_stream(seed, 0)
+    }
+}
+
+impl StableRng {
+    /// Like `seed_from_u64`, which takes the first four outputs of SplitMix64
+    /// seeded with `seed`, but takes outputs `4 * i` through `4 * i + 3`
+    /// instead. SplitMix64 can jump straight to any output, so this takes
+    /// `O(1)` time.
+    pub(crate) fn seed_from_u64_stream(seed: u64, i: u64) -> Self {
+        const GAMMA: u64 = 0x9e37_79b9_7f4a_7c15;
+        let mut x = seed.wrapping_add(i.wrapping_mul(4).wrapping_mul(GAMMA));
+        let mut s = [0_u64; 4];
+        for s in &mut s {
+            x = x.wrapping_add(GAMMA);
+            let mut z = x;
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+            *s = z ^ (z >> 31);
+        }
+        Self { s }
+    }
+}
+
+//////////
+// Test //
+//////////
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        atomic::AtomicBitScatter,
+        lanes::BitScatterX8,
+        lehmer::{LehmerPermuter, MAX_LEHMER_PERIOD},
+        slice::FastShuffle,
+        streams::Permuters,
+        subset::{random_subset_mask, random_subset_mask_exact},
+        weighted::WeightedPermuter,
+        BitScatter, Derangement, NoRepeat, Sattolo, Shuffle, ShuffleArray, ShuffleArrayIncremental,
+        ShuffleBag, SmallIndexPermutations, MAX_PERIOD,
+    };
+    use rand_xoshiro::Xoshiro256PlusPlus;
+    use std::fmt::{self, Write};
+
+    const GOLDEN_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden_vectors.txt");
+    const GOLDEN_VECTORS: &str = include_str!("../tests/golden_vectors.txt");
+    const SEEDS: [u64; 2] = [0, 0xDEAD_BEEF_F000_BA55];
+    const PERIODS: [u8; 4] = [1, 2, 7, MAX_PERIOD];
+    const NUM_PERIODS: usize = 3;
+    // Separates the covered outputs from the uncovered ones.
+    const UNCOVERED_HEADER: &str =
+        "# not covered by the compatibility promise, but must not change by accident";
+
+    fn rng(seed: u64) -> StableRng {
+        StableRng::seed_from_u64(seed)
+    }
+
+    fn push_line<T: fmt::Display>(
+        out: &mut String,
+        name: &str,
+        seed: u64,
+        n: u8,
+        values: impl Iterator<Item = T>,
+    ) {
+        write!(out, "{} seed={:#x} n={}:", name, seed, n).unwrap();
+        for value in values {
+            write!(out, " {}", value).unwrap();
+        }
+        out.push('\n');
+    }
+
+    fn push_periods(
+        out: &mut String,
+        name: &str,
+        seed: u64,
+        mut perm: impl SmallIndexPermutations,
+    ) {
+        let n = perm.period();
+        let num_idxs = NUM_PERIODS * n as usize;
+        push_line(out, name, seed, n, perm.iter().take(num_idxs));
+    }
+
+    // Every output covered by the compatibility promise, then every uncovered
+    // one, one line per (permuter, seed, n).
+    fn golden_vectors() -> String {
+        let mut out = String::new();
+        for seed in SEEDS {
+            let mut stable = rng(seed);
+            push_line(
+                &mut out,
+                "stable_rng_u32",
+                seed,
+                0,
+                (0..16).map(|_| stable.next_u32()),
+            );
+
+            for n in PERIODS {
+                push_periods(
+                    &mut out,
+                    "shuffle_array_incr",
+                    seed,
+                    ShuffleArrayIncremental::new(rng(seed), n),
+                );
+                push_periods(&mut out, "bit_scatter", seed, BitScatter::new(rng(seed), n));
+                push_periods(&mut out, "sattolo", seed, Sattolo::new(rng(seed), n));
+                if n > 1 {
+                    push_periods(
+                        &mut out,
+                        "derangement",
+                        seed,
+                        Derangement::new(rng(seed), n),
+                    );
+                    push_periods(
+                        &mut out,
+                        "no_repeat_bit_scatter",
+                        seed,
+                        NoRepeat::new(BitScatter::new(rng(seed), n)),
+                    );
+                }
+
+                let mut buf = vec![0_u8; NUM_PERIODS * n as usize];
+                BitScatter::new(rng(seed), n).fill(&mut buf);
+                push_line(&mut out, "bit_scatter_fill", seed, n, buf.into_iter());
+
+                let mut buf = [0_u8; MAX_PERIOD as usize];
+                ShuffleArrayIncremental::new(rng(seed), n).fill_period(&mut buf);
+                push_line(
+                    &mut out,
+                    "shuffle_array_incr_fill_period",
+                    seed,
+                    n,
+                    buf[..n as usize].iter().copied(),
+                );
+
+                let mut lanes = BitScatterX8::new(rng(seed), n);
+                let idxs = (0..NUM_PERIODS * n as usize).flat_map(|_| lanes.next_indices());
+                push_line(&mut out, "bit_scatter_x8", seed, n, idxs);
+
+                let atomic = AtomicBitScatter::new(n);
+                let mut stable = rng(seed);
+                let idxs = (0..NUM_PERIODS * n as usize).map(|_| atomic.next_index(&mut stable));
+                push_line(&mut out, "atomic_bit_scatter", seed, n, idxs);
+
+                let mut items = (0..n).collect::<Vec<_>>();
+                let mut stable = rng(seed);
+                items.fast_shuffle(&mut stable);
+                let order = items
+                    .random_order_iter(&mut stable)
+                    .copied()
+                    .collect::<Vec<_>>();
+                push_line(
+                    &mut out,
+                    "fast_shuffle",
+                    seed,
+                    n,
+                    items.into_iter().chain(order),
+                );
+
+                let mut stable = rng(seed);
+                let masks =
+                    (0..=n).map(|k| format!("{:#x}", random_subset_mask(&mut stable, n, k)));
+                push_line(&mut out, "random_subset_mask", seed, n, masks);
+            }
+
+            let mut bag = ShuffleBag::new(rng(seed), &[2, 0, 1, 3]);
+            let n = bag.period();
+            let items = (0..NUM_PERIODS * n as usize).map(|_| bag.next_item());
+            push_line(&mut out, "shuffle_bag", seed, n, items);
+
+            let firsts = Permuters::from_seed(seed)
+                .streams()
+                .take(4)
+                .map(|mut rng| format!("{:#x}", rng.next_u64()));
+            push_line(&mut out, "permuters_streams", seed, 0, firsts);
+        }
+
+        out.push_str(UNCOVERED_HEADER);
+        out.push('\n');
+        for seed in SEEDS {
+            for n in PERIODS {
+                push_periods(&mut out, "shuffle", seed, Shuffle::new(rng(seed), n));
+                push_periods(
+                    &mut out,
+                    "shuffle_array",
+                    seed,
+                    ShuffleArray::new(rng(seed), n),
+                );
+                if n <= MAX_LEHMER_PERIOD {
+                    push_periods(&mut out, "lehmer", seed, LehmerPermuter::new(rng(seed), n));
+                }
+
+                let mut stable = rng(seed);
+                let masks =
+                    (0..=n).map(|k| format!("{:#x}", random_subset_mask_exact(&mut stable, n, k)));
+                push_line(&mut out, "random_subset_mask_exact", seed, n, masks);
+            }
+
+            let weights = [3.0, 0.0, 1.0, 2.5, 0.5];
+            push_periods(
+                &mut out,
+                "weighted",
+                seed,
+                WeightedPermuter::new(rng(seed), &weights),
+            );
+        }
+        out
+    }
+
+    // Set `FASTPERM_BLESS=1` to regenerate `tests/golden_vectors.txt` after an
+    // intentional, breaking change to the covered outputs.
+    #[test]
+    fn golden_vectors_unchanged() {
+        let actual = golden_vectors();
+        if std::env::var_os("FASTPERM_BLESS").is_some() {
+            std::fs::write(GOLDEN_PATH, &actual).unwrap();
+            return;
+        }
+        let mut covered = true;
+        for (expected, actual) in GOLDEN_VECTORS.lines().zip(actual.lines()) {
+            covered &= expected != UNCOVERED_HEADER;
+            if covered {
+                assert_eq!(
+                    expected, actual,
+                    "output changed; this breaks the stability promise"
+                );
+            } else {
+                assert_eq!(
+                    expected, actual,
+                    "output changed; if that's intended, re-bless the golden vectors"
+                );
+            }
+        }
+        assert_eq!(GOLDEN_VECTORS.lines().count(), actual.lines().count());
+    }
+
+    #[test]
+    fn test_stable_rng_eq_xoshiro256plusplus() {
+        for seed in SEEDS {
+            let mut stable = rng(seed);
+            let mut xoshiro = Xoshiro256PlusPlus::seed_from_u64(seed);
+            for _ in 0..64 {
+                assert_eq!(xoshiro.next_u64(), stable.next_u64());
+                assert_eq!(xoshiro.next_u32(), stable.next_u32());
+            }
+            stable.jump();
+            xoshiro.jump();
+            assert_eq!(xoshiro.next_u64(), stable.next_u64());
+
+            let mut seed_bytes = [0_u8; 32];
+            xoshiro.fill_bytes(&mut seed_bytes);
+            let mut stable = StableRng::from_seed(seed_bytes);
+            let mut xoshiro = Xoshiro256PlusPlus::from_seed(seed_bytes);
+            let (mut a, mut b) = ([0_u8; 13], [0_u8; 13]);
+            stable.fill_bytes(&mut a);
+            xoshiro.fill_bytes(&mut b);
+            assert_eq!(b, a);
+        }
+    }
+}
@@ -0,0 +1,146 @@
+use crate::stable::StableRng;
+
+/// The rng behind each stream. Its output is covered by the crate's
+/// [stability promise](crate::stable), so streams are reproducible across
+/// runs, platforms and releases.
+pub type StreamRng = StableRng;
+
+/// Derive many independent, reproducible rng streams from a single seed, e.g.,
+/// one per worker, each driving its own permuter.
+///
+/// Stream `i` is a [`StableRng`] seeded from outputs `4 * i` through
+/// `4 * i + 3` of SplitMix64 seeded with `seed`, so stream 0 is
+/// `StableRng::seed_from_u64(seed)`, and any stream takes `O(1)` time to get.
+/// The first `2^62` streams all start from distinct states. Unlike
+/// [`StableRng::jump`], that doesn't rule out two streams overlapping, but
+/// with `2^256` states, the chance that any of `k` streams drawing `m`
+/// numbers each overlap is only about `k^2 * m / 2^256`. Any permuter built
+/// on a stream is as independent of the permuters on the other streams as
+/// the streams themselves.
+///
+/// ## Example
+///
+/// ```rust
+/// use fastperm::{streams::Permuters, BitScatter, ShuffleArrayIncremental, SmallIndexPermutations};
+///
+/// let permuters = Permuters::from_seed(0xDEAD_BEEF);
+/// let mut workers = permuters
+///     .streams()
+///     .take(4)
+///     .map(|rng| BitScatter::new(rng, 16))
+///     .collect::<Vec<_>>();
+///
+/// // streams are reproducible, and any permuter can use them
+/// let mut worker_2 = BitScatter::new(permuters.stream(2), 16);
+/// assert!(workers[2].iter_period().eq(worker_2.iter_period()));
+/// let _ = ShuffleArrayIncremental::new(permuters.stream(5), 16);
+/// ```
+#[derive(Clone, Debug)]
+pub struct Permuters {
+    seed: u64,
+}
+
+impl Permuters {
+    pub fn from_seed(seed: u64) -> Self {
+        Self { seed }
+    }
+
+    /// The rng for stream `i`, in `O(1)` time. Streams repeat after `2^62`,
+    /// i.e., stream `i + 2^62` is stream `i`.
+    pub fn stream(&self, i: u64) -> StreamRng {
+        StreamRng::seed_from_u64_stream(self.seed, i)
+    }
+
+    /// An iterator over the rngs for streams `0, 1, 2, ...`.
+    pub fn streams(&self) -> Streams {
+        Streams {
+            seed: self.seed,
+            next: 0,
+        }
+    }
+}
+
+/// See [`Permuters::streams`].
+#[derive(Clone, Debug)]
+pub struct Streams {
+    seed: u64,
+    next: u64,
+}
+
+impl Iterator for Streams {
+    type Item = StreamRng;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        let rng = StreamRng::seed_from_u64_stream(self.seed, self.next);
+        self.next = self.next.wrapping_add(1);
+        Some(rng)
+    }
+}
+
+//////////
+// Test //
+//////////
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::{RngCore, SeedableRng};
+
+    #[test]
+    fn test_stream_eq_streams() {
+        let permuters = Permuters::from_seed(0xDEAD_BEEF_F000_BA55);
+        for (i, mut rng) in permuters.streams().take(20).enumerate() {
+            let mut expected = permuters.stream(i as u64);
+            for _ in 0..8 {
+                assert_eq!(expected.next_u64(), rng.next_u64());
+            }
+        }
+    }
+
+    #[test]
+    fn test_streams_are_distinct() {
+        let permuters = Permuters::from_seed(0xDEAD_BEEF_F000_BA55);
+        let mut firsts = permuters
+            .streams()
+            .take(1000)
+            .map(|mut rng| rng.next_u64())
+            .collect::<Vec<_>>();
+        firsts.sort_unstable();
+        firsts.dedup();
+        assert_eq!(1000, firsts.len());
+    }
+
+    #[test]
+    fn test_stream_is_constant_time() {
+        // would take hours with jumps
+        let permuters = Permuters::from_seed(0xDEAD_BEEF_F000_BA55);
+        let mut rng = permuters.stream(u64::MAX);
+        let mut expected = permuters.stream((1 << 62) - 1);
+        assert_eq!(expected.next_u64(), rng.next_u64());
+    }
+
+    #[test]
+    fn test_stream_0_is_seeded_rng() {
+        let mut rng = Permuters::from_seed(42).stream(0);
+        let mut expected = StableRng::seed_from_u64(42);
+        assert_eq!(expected.next_u64(), rng.next_u64());
+    }
+
+    #[test]
+    fn test_streams_golden() {
+        // streams must be reproducible across runs and platforms
+        let permuters = Permuters::from_seed(0xDEAD_BEEF_F000_BA55);
+        let firsts = permuters
+            .streams()
+            .take(3)
+            .map(|mut rng| rng.next_u64())
+            .collect::<Vec<_>>();
+        let expected = vec![
+            0x400b_3913_a735_cb03,
+            0xb5b8_95b5_dbe4_758c,
+            0xe799_8a02_f7af_2a4b,
+        ];
+        assert_eq!(expected, firsts);
+    }
+}
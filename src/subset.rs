@@ -0,0 +1,128 @@
+use crate::{fastmap32, index_mask, MAX_PERIOD};
+use rand::Rng;
+
+/// Sample a random `k`-subset of `[0, n)` as a bit mask, using Floyd's
+/// algorithm.
+///
+/// Like the permuters, this maps each `u32` sample into range with
+/// [`fastmap32`](https://lemire.me/blog/2016/06/27/a-fast-alternative-to-the-modulo-reduction/),
+/// so subsets are only uniform up to a bias of about `n / 2^32`. Use
+/// [`random_subset_mask_exact`] when that matters.
+///
+/// Panics if `n > MAX_PERIOD` or `k > n`.
+///
+/// ## Example
+///
+/// ```rust
+/// use fastperm::subset::random_subset_mask;
+/// use rand::{rngs::SmallRng, SeedableRng};
+///
+/// let mut rng = SmallRng::seed_from_u64(123);
+/// let canaries = random_subset_mask(&mut rng, 40, 3);
+/// assert_eq!(3, canaries.count_ones());
+/// assert!(canaries < 1 << 40);
+/// ```
+#[inline]
+pub fn random_subset_mask<R: Rng + ?Sized>(rng: &mut R, n: u8, k: u8) -> u64 {
+    subset_mask(n, k, |j| fastmap32(rng.next_u32(), j + 1))
+}
+
+/// Like [`random_subset_mask`], but exactly uniform over all `k`-subsets, at
+/// the cost of occasionally rejecting and redrawing a sample.
+///
+/// Panics if `n > MAX_PERIOD` or `k > n`.
+#[inline]
+pub fn random_subset_mask_exact<R: Rng + ?Sized>(rng: &mut R, n: u8, k: u8) -> u64 {
+    subset_mask(n, k, |j| rng.gen_range(0..=j))
+}
+
+// `sample_incl(j)` should return a uniform sample in `[0, j]`.
+#[inline(always)]
+fn subset_mask(n: u8, k: u8, sample_incl: impl FnMut(u32) -> u32) -> u64 {
+    assert!(n <= MAX_PERIOD && k <= n);
+    if k == n {
+        return if n == 0 { 0 } else { index_mask(n as u32) };
+    }
+    // Floyd's algorithm takes one sample per chosen index, so for large `k`
+    // it's cheaper to choose the `n - k` indices to leave out instead.
+    if k > n / 2 {
+        index_mask(n as u32) & !floyd(n, n - k, sample_incl)
+    } else {
+        floyd(n, k, sample_incl)
+    }
+}
+
+#[inline(always)]
+fn floyd(n: u8, k: u8, mut sample_incl: impl FnMut(u32) -> u32) -> u64 {
+    let mut mask = 0_u64;
+    for j in (n - k)..n {
+        let t = sample_incl(j as u32);
+        // if `t` is already in the subset, `j` can't be, since every earlier
+        // sample was `< j`.
+        let bit = if mask & (1 << t) == 0 { t } else { j as u32 };
+        mask |= 1 << bit;
+    }
+    mask
+}
+
+//////////
+// Test //
+//////////
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::arb_small_rng;
+    use proptest::prelude::*;
+    use rand::{rngs::SmallRng, SeedableRng};
+
+    fn arb_n_and_k() -> impl Strategy<Value = (u8, u8)> {
+        (0..=MAX_PERIOD).prop_flat_map(|n| (Just(n), 0..=n))
+    }
+
+    fn assert_subset_uniform(mut sample: impl FnMut() -> u64) {
+        // C(5, 2) == 10 subsets
+        let num_samples = 100_000;
+        let mut counts = std::collections::HashMap::<u64, usize>::new();
+        for _ in 0..num_samples {
+            *counts.entry(sample()).or_default() += 1;
+        }
+        assert_eq!(10, counts.len());
+        let expected = num_samples / 10;
+        for (mask, count) in counts {
+            assert!(
+                count.abs_diff(expected) < expected / 20,
+                "{:05b}: {} vs expected {}",
+                mask,
+                count,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_random_subset_mask_uniform() {
+        let mut rng = SmallRng::seed_from_u64(0xDEAD_BEEF_F000_BA55);
+        assert_subset_uniform(|| random_subset_mask(&mut rng, 5, 2));
+        // exercises the complement path
+        assert_subset_uniform(|| random_subset_mask(&mut rng, 5, 3) ^ 0b11111);
+        assert_subset_uniform(|| random_subset_mask_exact(&mut rng, 5, 2));
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(10000))]
+
+        #[test]
+        fn random_subset_mask_has_k_bits((n, k) in arb_n_and_k(), mut rng in arb_small_rng()) {
+            let valid_mask = if n == 0 { 0 } else { index_mask(n as u32) };
+
+            let mask = random_subset_mask(&mut rng, n, k);
+            assert_eq!(k as u32, mask.count_ones());
+            assert_eq!(0, mask & !valid_mask);
+
+            let mask = random_subset_mask_exact(&mut rng, n, k);
+            assert_eq!(k as u32, mask.count_ones());
+            assert_eq!(0, mask & !valid_mask);
+        }
+    }
+}
@@ -0,0 +1,288 @@
+use crate::{fastmap32, index_mask, select64::select64, SmallIndexPermutations, MAX_PERIOD};
+use rand::Rng;
+
+/// A permuter that visits every index once per period, in a random order
+/// biased by per-index weights.
+///
+/// Each step picks one of the indices not yet chosen this period with
+/// probability proportional to its weight, tracking the unchosen indices in a
+/// mask like [`BitScatter`](crate::BitScatter). This gives the same order
+/// distribution as Efraimidis–Spirakis weighted sampling without replacement,
+/// i.e., sorting by `u_i^(1 / w_i)` for uniform `u_i`. Zero-weight indices come
+/// last in each period, in uniformly random order.
+///
+/// Weights can be any type that converts losslessly into an `f64`, e.g., `f32`
+/// or `u32`.
+///
+/// ## Example
+///
+/// ```rust
+/// use fastperm::{weighted::WeightedPermuter, SmallIndexPermutations};
+/// use rand::{rngs::SmallRng, SeedableRng};
+///
+/// // backend 1 has no capacity, so it's always visited last
+/// let mut backends = WeightedPermuter::new(SmallRng::seed_from_u64(123), &[4_u32, 0, 1, 2]);
+/// assert_eq!(Some(1), backends.iter_period().last());
+///
+/// backends.set_weights(&[1.0_f32, 1.0, 0.0, 1.0]);
+/// assert_eq!(Some(2), backends.iter_period().last());
+/// ```
+pub struct WeightedPermuter<R> {
+    rng: R,
+    weights: [f64; MAX_PERIOD as usize],
+    // indices with a non-zero weight
+    weighted_mask: u64,
+    unchosen_mask: u64,
+    n: u8,
+    // number of remaining indices, i.e., number of one bits in the unchosen mask
+    m: u8,
+}
+
+impl<R: Rng> WeightedPermuter<R> {
+    /// Panics if `weights` is empty, longer than `MAX_PERIOD`, contains a
+    /// negative or non-finite weight, or sums to more than `f64::MAX`.
+    pub fn new<W: Copy + Into<f64>>(rng: R, weights: &[W]) -> Self {
+        let mut this = Self {
+            rng,
+            weights: [0.0; MAX_PERIOD as usize],
+            weighted_mask: 0,
+            unchosen_mask: 0,
+            n: 0,
+            m: 0,
+        };
+        this.set_weights(weights);
+        this
+    }
+
+    /// Replace the weights and start a new period. The number of weights may
+    /// change, which changes the period.
+    ///
+    /// Panics under the same conditions as [`WeightedPermuter::new`].
+    pub fn set_weights<W: Copy + Into<f64>>(&mut self, weights: &[W]) {
+        let n = weights.len();
+        assert!(n <= MAX_PERIOD as usize && n > 0);
+
+        self.weighted_mask = 0;
+        for (i, &weight) in weights.iter().enumerate() {
+            let weight = weight.into();
+            assert!(
+                weight.is_finite() && weight >= 0.0,
+                "weights must be finite and non-negative: index: {}, weight: {}",
+                i,
+                weight,
+            );
+            self.weights[i] = weight;
+            if weight > 0.0 {
+                self.weighted_mask |= 1 << i;
+            }
+        }
+        assert!(
+            self.weights[..n].iter().sum::<f64>().is_finite(),
+            "weights must sum to a finite total",
+        );
+        self.n = n as u8;
+        self.reset();
+    }
+
+    pub fn weights(&self) -> &[f64] {
+        &self.weights[..self.n as usize]
+    }
+
+    // The total weight of the indices in `mask`. This is summed afresh for each
+    // pick instead of kept as a running total, since subtracting a huge weight
+    // from the running total would also wipe out any much smaller ones.
+    #[inline]
+    fn total_weight(&self, mut mask: u64) -> f64 {
+        let mut total = 0.0;
+        while mask != 0 {
+            total += self.weights[mask.trailing_zeros() as usize];
+            mask &= mask - 1;
+        }
+        total
+    }
+
+    // Pick one of the `candidates` (all with non-zero weight, together weighing
+    // `total`) with probability proportional to its weight.
+    #[inline]
+    fn sample_weighted(&mut self, candidates: u64, total: f64) -> u8 {
+        let mut target = self.rng.gen::<f64>() * total;
+        let mut mask = candidates;
+        loop {
+            let idx = mask.trailing_zeros() as u8;
+            mask &= mask - 1;
+            let weight = self.weights[idx as usize];
+            // rounding can leave a little `target` left over after the last
+            // candidate; just take the last one.
+            if target < weight || mask == 0 {
+                return idx;
+            }
+            target -= weight;
+        }
+    }
+}
+
+impl<R: Rng> SmallIndexPermutations for WeightedPermuter<R> {
+    #[inline(always)]
+    fn period(&self) -> u8 {
+        self.n
+    }
+
+    fn reset(&mut self) {
+        self.m = self.n;
+        self.unchosen_mask = index_mask(self.n as u32);
+    }
+
+    fn next_index(&mut self) -> u8 {
+        if self.m == 0 {
+            self.reset();
+        }
+
+        let candidates = self.unchosen_mask & self.weighted_mask;
+        let idx = if candidates != 0 {
+            self.sample_weighted(candidates, self.total_weight(candidates))
+        } else {
+            // only zero weights left, so pick uniformly like `BitScatter`
+            let idx_mspace = fastmap32(self.rng.next_u32(), self.m as u32) as u8;
+            select64(idx_mspace, self.unchosen_mask)
+        };
+
+        self.unchosen_mask &= !(1_u64 << (idx as u32));
+        self.m -= 1;
+        idx
+    }
+}
+
+//////////
+// Test //
+//////////
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{fill_mask, test::arb_small_rng};
+    use proptest::prelude::*;
+    use rand::{rngs::SmallRng, SeedableRng};
+
+    fn arb_weights() -> impl Strategy<Value = Vec<u32>> {
+        proptest::collection::vec(
+            prop_oneof![Just(0_u32), 1..1000_u32],
+            1..=MAX_PERIOD as usize,
+        )
+    }
+
+    #[test]
+    fn test_weighted_first_index_distribution() {
+        let weights = [1_u32, 2, 3, 4, 0, 10];
+        let total = weights.iter().sum::<u32>() as f64;
+        let mut weighted = WeightedPermuter::new(SmallRng::seed_from_u64(0xF00D), &weights);
+
+        let num_periods = 200_000;
+        let mut counts = [0_usize; 6];
+        for _ in 0..num_periods {
+            counts[weighted.iter().next().unwrap() as usize] += 1;
+        }
+
+        for (&weight, &count) in weights.iter().zip(&counts) {
+            let expected = num_periods as f64 * weight as f64 / total;
+            assert!(
+                (count as f64 - expected).abs() <= expected * 0.05,
+                "weight: {}, count: {}, expected: {}",
+                weight,
+                count,
+                expected,
+            );
+        }
+    }
+
+    #[test]
+    fn test_weighted_second_index_distribution() {
+        // check the conditional probabilities of the second index, given
+        // the remaining weights after the first.
+        let weights = [3.0_f32, 2.0, 1.0];
+        let mut weighted = WeightedPermuter::new(SmallRng::seed_from_u64(0xF00D), &weights);
+
+        let num_periods = 200_000;
+        let mut counts = [0_usize; 3];
+        for _ in 0..num_periods {
+            counts[weighted.iter().nth(1).unwrap() as usize] += 1;
+        }
+
+        // P(second = j) = sum_{i != j} P(first = i) * w_j / (total - w_i)
+        let total = 6.0;
+        let mut p_second = [0.0; 3];
+        for (j, p) in p_second.iter_mut().enumerate() {
+            for i in (0..3).filter(|&i| i != j) {
+                let p_first = weights[i] as f64 / total;
+                *p += p_first * weights[j] as f64 / (total - weights[i] as f64);
+            }
+        }
+        for (&p, &count) in p_second.iter().zip(&counts) {
+            let expected = num_periods as f64 * p;
+            assert!((count as f64 - expected).abs() <= expected * 0.05);
+        }
+    }
+
+    #[test]
+    fn test_weighted_wide_weight_range() {
+        // once the huge weight is chosen, the rest should still be picked 1:3
+        let weights = [1e17, 1.0, 3.0];
+        let mut weighted = WeightedPermuter::new(SmallRng::seed_from_u64(0xF00D), &weights);
+
+        let num_periods = 20_000;
+        let mut counts = [0_usize; 3];
+        for _ in 0..num_periods {
+            let period = weighted.iter_period().collect::<Vec<_>>();
+            assert_eq!(0, period[0]);
+            counts[period[1] as usize] += 1;
+        }
+        let expected = num_periods as f64 / 4.0;
+        assert!(
+            (counts[1] as f64 - expected).abs() <= expected * 0.05,
+            "counts: {:?}",
+            counts
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "finite total")]
+    fn test_weighted_infinite_total() {
+        WeightedPermuter::new(SmallRng::seed_from_u64(0xF00D), &[f64::MAX, f64::MAX]);
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(2000))]
+
+        #[test]
+        fn weighted_is_permutation(weights in arb_weights(), rng in arb_small_rng()) {
+            let n = weights.len() as u8;
+            let mut weighted = WeightedPermuter::new(rng, &weights);
+            for _ in 0..2 {
+                let idxs = weighted.iter_period().collect::<Vec<_>>();
+                let (mask, count) = fill_mask(idxs.iter().copied());
+                assert_eq!(n, count);
+                assert_eq!(index_mask(n as u32), mask);
+
+                // zero weights always come last
+                let num_weighted = weights.iter().filter(|&&w| w > 0).count();
+                assert!(idxs[..num_weighted].iter().all(|&idx| weights[idx as usize] > 0));
+            }
+        }
+
+        #[test]
+        fn weighted_set_weights(
+            weights1 in arb_weights(),
+            weights2 in arb_weights(),
+            rng in arb_small_rng(),
+        ) {
+            let mut weighted = WeightedPermuter::new(rng, &weights1);
+            weighted.next_index();
+            weighted.set_weights(&weights2);
+
+            let n = weights2.len() as u8;
+            assert_eq!(n, weighted.period());
+            let (mask, count) = fill_mask(weighted.iter_period());
+            assert_eq!(n, count);
+            assert_eq!(index_mask(n as u32), mask);
+        }
+    }
+}